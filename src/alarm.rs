@@ -0,0 +1,247 @@
+use std::f32::consts::TAU;
+use time::{Duration, OffsetDateTime};
+
+/// Countdown to each command of the schedule.
+/// Every step alerts once, when its departure time comes.
+#[derive(Clone, Debug)]
+pub struct TripTimer {
+    steps: Vec<(String, OffsetDateTime)>,
+    /// Steps before this one have already alerted
+    alerted: usize,
+}
+
+impl TripTimer {
+    /// Steps, which are already due, don't alert
+    pub fn new(steps: Vec<(String, OffsetDateTime)>, now: OffsetDateTime) -> Self {
+        let alerted = steps.iter().take_while(|(_, at)| *at <= now).count();
+        Self { steps, alerted }
+    }
+
+    /// Step, which is due and hasn't alerted yet. Several steps due at once alert as the last one.
+    pub fn poll(&mut self, now: OffsetDateTime) -> Option<&str> {
+        let due = self.steps.iter().take_while(|(_, at)| *at <= now).count();
+        if due <= self.alerted {
+            return None;
+        }
+        self.alerted = due;
+        Some(&self.steps[due - 1].0)
+    }
+
+    /// Next step and time left until it
+    pub fn next(&self, now: OffsetDateTime) -> Option<(&str, Duration)> {
+        self.steps
+            .iter()
+            .find(|(_, at)| *at > now)
+            .map(|(command, at)| (command.as_str(), *at - now))
+    }
+
+    /// Timer needs no repaints after the last step
+    pub fn is_finished(&self, now: OffsetDateTime) -> bool {
+        self.next(now).is_none()
+    }
+}
+
+/// Short beep as a WAV file: 8 kHz, 8 bit, mono
+pub fn beep_wav() -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 8000;
+    const FREQUENCY: f32 = 880.0;
+    const SAMPLES: u32 = SAMPLE_RATE * 3 / 10;
+    let mut wav = Vec::with_capacity(44 + SAMPLES as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + SAMPLES).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    // Byte rate, block align, bits per sample
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&SAMPLES.to_le_bytes());
+    wav.extend((0..SAMPLES).map(|i| {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        // Fade out to avoid a click at the end
+        let volume = 100.0 * (1.0 - i as f32 / SAMPLES as f32);
+        (128.0 + volume * (TAU * FREQUENCY * t).sin()) as u8
+    }));
+    wav
+}
+
+/// [`beep_wav`] as `data:` URL
+#[cfg(any(target_arch = "wasm32", test))]
+pub fn beep_url() -> String {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    format!("data:audio/wav;base64,{}", STANDARD.encode(beep_wav()))
+}
+
+/// Plays [`beep_wav`] without blocking the UI
+#[cfg(target_arch = "wasm32")]
+pub fn play_beep() {
+    let played = web_sys::HtmlAudioElement::new_with_src(&beep_url())
+        .and_then(|audio| audio.play())
+        .is_ok();
+    if !played {
+        log::warn!("Can not play alarm sound");
+    }
+}
+
+/// Plays [`beep_wav`] with the system player without blocking the UI
+#[cfg(not(target_arch = "wasm32"))]
+pub fn play_beep() {
+    use std::process::Command;
+
+    let path = std::env::temp_dir().join("marshrutka-beep.wav");
+    if let Err(err) = std::fs::write(&path, beep_wav()) {
+        log::warn!("Can not write alarm sound: {err}");
+        return;
+    }
+    let players = if cfg!(target_os = "macos") {
+        let mut afplay = Command::new("afplay");
+        afplay.arg(&path);
+        vec![afplay]
+    } else if cfg!(target_os = "windows") {
+        // Script is constant, the path is passed through the environment
+        let mut powershell = Command::new("powershell");
+        powershell
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "(New-Object Media.SoundPlayer $env:MARSHRUTKA_BEEP).PlaySync()",
+            ])
+            .env("MARSHRUTKA_BEEP", &path);
+        vec![powershell]
+    } else {
+        let mut paplay = Command::new("paplay");
+        paplay.arg(&path);
+        let mut aplay = Command::new("aplay");
+        aplay.arg("-q").arg(&path);
+        vec![paplay, aplay]
+    };
+    spawn_first(players);
+}
+
+/// Shows a toast as Windows PowerShell, texts are taken from the environment
+const WINDOWS_TOAST_SCRIPT: &str = r#"
+$manager = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$toast = $manager::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$texts = $toast.GetElementsByTagName('text')
+$texts.Item(0).AppendChild($toast.CreateTextNode($env:MARSHRUTKA_TITLE)) > $null
+$texts.Item(1).AppendChild($toast.CreateTextNode($env:MARSHRUTKA_BODY)) > $null
+$app = '{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe'
+$manager::CreateToastNotifier($app).Show([Windows.UI.Notifications.ToastNotification]::new($toast))
+"#;
+
+/// Desktop notification, only on native
+#[cfg(target_arch = "wasm32")]
+pub fn notify(_title: &str, _body: &str) {}
+
+/// Desktop notification via `notify-send` on Linux, `osascript` on macOS
+/// and a PowerShell toast on Windows
+#[cfg(not(target_arch = "wasm32"))]
+pub fn notify(title: &str, body: &str) {
+    use std::process::Command;
+
+    // Texts are passed as arguments or environment, so they are never parsed as code
+    if cfg!(target_os = "macos") {
+        let mut osascript = Command::new("osascript");
+        osascript.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ]);
+        spawn_first(vec![osascript]);
+    } else if cfg!(target_os = "windows") {
+        let mut powershell = Command::new("powershell");
+        powershell
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                WINDOWS_TOAST_SCRIPT,
+            ])
+            .env("MARSHRUTKA_TITLE", title)
+            .env("MARSHRUTKA_BODY", body);
+        spawn_first(vec![powershell]);
+    } else {
+        let mut notify_send = Command::new("notify-send");
+        notify_send.args([title, body]);
+        spawn_first(vec![notify_send]);
+    }
+}
+
+/// Runs the first program, which exists, in the background
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_first(programs: Vec<std::process::Command>) {
+    std::thread::spawn(move || {
+        for mut program in programs {
+            // GUI app should not flash a console window for every alert
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::process::CommandExt;
+                const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+                program.creation_flags(CREATE_NO_WINDOW);
+            }
+            match program.status() {
+                Ok(_) => return,
+                Err(err) => log::debug!("Can not run {:?}: {err}", program.get_program()),
+            }
+        }
+        log::warn!("Can not run any of alarm programs");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn alerts_once_per_step() {
+        let start = datetime!(2025-01-01 12:00:00 UTC);
+        let steps = vec![
+            ("/go_direct_b_1_1".to_string(), start),
+            ("/go_direct_b_2_2".to_string(), start + Duration::minutes(5)),
+            ("/use_soe".to_string(), start + Duration::minutes(6)),
+        ];
+        let mut timer = TripTimer::new(steps, start - Duration::minutes(1));
+        assert_eq!(
+            timer.next(start - Duration::seconds(30)),
+            Some(("/go_direct_b_1_1", Duration::seconds(30)))
+        );
+        assert_eq!(timer.poll(start - Duration::seconds(1)), None);
+        assert_eq!(timer.poll(start), Some("/go_direct_b_1_1"));
+        assert_eq!(timer.poll(start + Duration::seconds(1)), None);
+        // Both steps became due while the app was asleep
+        assert_eq!(timer.poll(start + Duration::minutes(7)), Some("/use_soe"));
+        assert!(timer.is_finished(start + Duration::minutes(7)));
+    }
+
+    #[test]
+    fn started_late() {
+        let start = datetime!(2025-01-01 12:00:00 UTC);
+        let mut timer = TripTimer::new(
+            vec![("/go_direct_b_1_1".to_string(), start)],
+            start + Duration::seconds(1),
+        );
+        assert_eq!(timer.poll(start + Duration::seconds(2)), None);
+        assert!(timer.is_finished(start + Duration::seconds(2)));
+    }
+
+    #[test]
+    fn beep() {
+        let wav = beep_wav();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 2400);
+        assert!(beep_url().starts_with("data:audio/wav;base64,UklGR"));
+    }
+}
@@ -1,37 +1,114 @@
+use crate::alarm::{self, TripTimer};
+use crate::bookmark::{Bookmark, Bookmarks};
+use crate::calendar::{Calendar, CalendarEvent};
+use crate::cell::{CellElement, CellMarks};
+use crate::cell_input::CellInput;
+use crate::clock::{Clock, ClockZone, format_duration};
 use crate::consts::{
-    BLEACH_ALPHA, CELL_SIZE, DEFAULT_MAP_URL, FONT_CENTER, FONT_CENTER_SIZE, FONT_CORNER,
-    FONT_CORNER_SIZE,
+    ALARM_FLASH_SECS, ARROW_LEGEND_WIDTH, ARROW_TIP_ANGLE, ARROW_WIDTH, BLEACH_ALPHA,
+    BOOKMARK_MARKER_RADIUS, BOOKMARKS_FILE, CELL_INPUT_WIDTH, CELL_SIZE, DEFAULT_MAP_URL,
+    EMOJI_CORNER_SIZE, EXPORT_PNG_FILE, EXPORT_SVG_FILE, FONT_CENTER, FONT_CENTER_SIZE,
+    FONT_CORNER, FONT_CORNER_SIZE, MAP_CHANGE_HIGHLIGHT_SECS, NOTE_BADGE_COLOR, SCHEDULE_ICS_FILE,
+    TOGGLE_CARAVANS_SHORTCUT, TOGGLE_SFM_SHORTCUT, TOGGLE_SHQ_SHORTCUT, TOGGLE_SOE_SHORTCUT,
 };
-use crate::cost::{AggregatedCost, Command, CostComparator, TotalCost};
+use crate::cost::{AggregatedCost, Command, CostComparator, MoveKind, TotalCost};
 use crate::deep_link::{LINK_TO_SUPPORT_CHAT, send_command, send_command_to_bot};
 use crate::emoji::EmojiMap;
-use crate::grid::{MapGrid, MapGridResponse, arrow};
+use crate::export::MapExport;
+use crate::file_dialog;
+use crate::filter::CellFilter;
+use crate::grid::{ArrowLanes, MapGrid, MapGridResponse, MapView, arrow};
+use crate::history::{MAP_HISTORY_KEY, MapHistory, MapSnapshot};
 use crate::homeland::Homeland;
 use crate::index::{CellIndex, CellIndexBuilder, CellIndexCommandSuffix, CellIndexLiteral};
+use crate::note::{CellNote, CellNotes};
 use crate::pathfinder::FindPath;
 use crate::skill::{Fleetfoot, RouteGuru, Skill};
+use crate::theme::{Theme, label_text_color, pinned_route_color};
 use crate::translation::Translation;
+use crate::trip::Trip;
+use crate::validation::{MapIssue, validate};
 use eframe::CreationContext;
 use eframe::emath::Align;
+use egui::ahash::HashSet;
 use egui::emath::Rot2;
 use egui::load::BytesPoll;
 use egui::scroll_area::ScrollBarVisibility;
 use egui::{
-    Color32, FontId, Id, Image, ImageButton, InnerResponse, Layout, ScrollArea, TextBuffer,
-    TextStyle, Ui, Visuals, Widget,
+    Align2, Button, Color32, FontId, Id, Image, ImageButton, InnerResponse, Layout, Painter, Pos2,
+    ScrollArea, Sense, TextBuffer, TextStyle, Ui, Vec2, Widget,
 };
 use rust_i18n::{set_locale, t};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::borrow::Cow;
 use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::Display;
 use std::iter;
+use std::path::PathBuf;
 use std::rc::Rc;
-use strum::IntoEnumIterator;
-use time::convert::{Hour, Second};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
+use time::convert::{Day, Hour, Minute, Second};
 use time::macros::format_description;
-use time::{Duration, Time};
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+#[derive(Copy, Clone)]
+struct MapUpdateNotice {
+    changed_cells: usize,
+    route_affected: bool,
+}
+
+/// Settings, which distinguish pinned routes
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+struct RouteOptions {
+    sort_by: (CostComparator, CostComparator),
+    use_soe: bool,
+    use_shq: bool,
+    use_sfm: bool,
+    use_caravans: bool,
+}
+
+/// Which end of the route the schedule is anchored to
+#[derive(Copy, Clone, Default, PartialEq, Deserialize, Serialize, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+enum ScheduleMode {
+    /// Steps are scheduled backward from the arrival time
+    #[default]
+    ArriveAt,
+    /// Steps are scheduled forward from the departure time
+    DepartAt,
+}
+
+#[derive(Deserialize, Serialize)]
+struct PinnedRoute {
+    name: String,
+    options: RouteOptions,
+}
+
+struct HistoryView {
+    position: usize,
+    grid: MapGrid,
+    /// Cells, which differ from the live map
+    changed_cells: HashSet<CellIndex>,
+}
+
+/// Bookmark, which is being added in the bookmarks window
+#[derive(Default)]
+struct NewBookmark {
+    name: String,
+    cell: Option<CellIndex>,
+    cell_text: String,
+}
+
+/// Note, which is being edited in the notes window
+#[derive(Default)]
+struct NoteEdit {
+    cell: Option<CellIndex>,
+    cell_text: String,
+    text: String,
+    tags_text: String,
+}
 
 #[derive(Deserialize, Serialize)]
 #[serde(default)]
@@ -40,10 +117,18 @@ pub struct MarshrutkaApp {
     emojis: OnceCell<EmojiMap>,
     show_settings: bool,
     show_about: bool,
+    show_diagnostics: bool,
+    show_bookmarks: bool,
+    show_notes: bool,
     #[serde(skip)]
     grid: Option<MapGrid>,
+    #[serde(skip)]
+    map_issues: Vec<MapIssue>,
     from: Option<CellIndex>,
     to: Option<CellIndex>,
+    target_filter: Option<CellFilter>,
+    #[serde(skip)]
+    available_filters: Vec<CellFilter>,
     homeland: Homeland,
     #[serde(skip)]
     need_to_save: bool,
@@ -56,12 +141,89 @@ pub struct MarshrutkaApp {
     use_shq: bool,
     use_sfm: bool,
     use_caravans: bool,
-    arrive_at: Time,
+    schedule_mode: ScheduleMode,
+    /// Arrival or departure time, depending on `schedule_mode`
+    #[serde(alias = "arrive_at")]
+    schedule_time: Time,
+    #[serde(alias = "arrive_on")]
+    schedule_date: Date,
+    /// Time zone of `schedule_time` and the schedule
+    clock_zone: ClockZone,
+    /// Chat Wars runs on Moscow time
+    server_utc_offset: UtcOffset,
+    /// Minutes before each step, when calendar reminds about it
+    calendar_reminder: u32,
     pause_between_steps: u32,
     #[serde(skip)]
     path: Option<Rc<TotalCost>>,
     map_url: String,
+    /// Directory with user supplied emojis, empty for bundled only
+    emoji_dir: String,
+    /// Minutes, 0 disables refresh
+    map_refresh_interval: u32,
+    #[serde(skip)]
+    map_loaded_at: f64,
+    #[serde(skip)]
+    map_refreshing: bool,
+    #[serde(skip)]
+    changed_cells: HashSet<CellIndex>,
+    #[serde(skip)]
+    map_changed_at: f64,
+    #[serde(skip)]
+    map_update_notice: Option<MapUpdateNotice>,
+    map_history_size: usize,
+    map_view: MapView,
+    pinned_routes: Vec<PinnedRoute>,
+    theme: Theme,
+    #[serde(skip)]
+    pinned_paths: Vec<Option<Rc<TotalCost>>>,
+    #[serde(skip)]
+    map_history: MapHistory,
+    #[serde(skip)]
+    need_to_save_history: bool,
+    #[serde(skip)]
+    history_view: Option<HistoryView>,
+    /// Cost of the route from `from` to the hovered cell
+    #[serde(skip)]
+    hover_cost: Option<(CellIndex, Option<TotalCost>)>,
+    #[serde(skip)]
+    from_text: String,
+    #[serde(skip)]
+    to_text: String,
+    bookmarks: Bookmarks,
+    #[serde(skip)]
+    new_bookmark: NewBookmark,
+    /// Result of the last bookmarks import or export
+    #[serde(skip)]
+    bookmarks_status: Option<Result<String, String>>,
+    /// Bookmarks to import on web, where files can not be opened
+    #[serde(skip)]
+    bookmarks_import_text: String,
+    /// Result of the last map or schedule export
+    #[serde(skip)]
+    export_status: Option<Result<String, String>>,
+    notes: CellNotes,
+    /// Cells with any of these tags are highlighted on the map
+    highlight_tags: BTreeSet<String>,
+    #[serde(skip)]
+    notes_query: String,
+    #[serde(skip)]
+    note_edit: NoteEdit,
     command_via_chat_link: bool,
+    #[serde(skip)]
+    trip_timer: Option<TripTimer>,
+    #[serde(skip)]
+    trip: Option<Trip>,
+    /// Cell, which the trip is re-planned from
+    #[serde(skip)]
+    trip_cell: Option<CellIndex>,
+    #[serde(skip)]
+    trip_cell_text: String,
+    /// Command, which is due, and app time until it is flashed
+    #[serde(skip)]
+    alarm: Option<(String, f64)>,
+    alarm_sound: bool,
+    alarm_notification: bool,
     route_guru_skill: u32,
     fleetfoot_skill: u32,
     translation: Translation,
@@ -74,15 +236,18 @@ impl MarshrutkaApp {
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        let result: MarshrutkaApp = if let Some(storage) = cc.storage {
+        let mut result: MarshrutkaApp = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
         } else {
             Default::default()
         };
+        if let Some(storage) = cc.storage {
+            result.map_history = eframe::get_value(storage, MAP_HISTORY_KEY).unwrap_or_default();
+        }
 
         set_locale(result.translation.to_locale_name());
         egui_extras::install_image_loaders(&cc.egui_ctx);
-        cc.egui_ctx.set_visuals(Visuals::light());
+        result.theme.apply(&cc.egui_ctx);
         cc.egui_ctx.all_styles_mut(|styles| {
             let body_font_family = TextStyle::Body.resolve(styles).family;
             styles.text_styles.insert(
@@ -93,14 +258,14 @@ impl MarshrutkaApp {
                 TextStyle::Name(FONT_CORNER.into()),
                 FontId::new(FONT_CORNER_SIZE, body_font_family),
             );
-            styles.visuals = Visuals::dark();
         });
 
         result
     }
 
     fn emojis(&self, ctx: &'_ egui::Context) -> &EmojiMap {
-        self.emojis.get_or_init(|| EmojiMap::new(ctx))
+        self.emojis
+            .get_or_init(|| new_emoji_map(ctx, &self.emoji_dir))
     }
 
     fn top_menu(&mut self, ctx: &egui::Context) {
@@ -113,6 +278,34 @@ impl MarshrutkaApp {
                         self.need_to_save = true;
                         ui.close_menu();
                     }
+                    if ui.button(t!("diagnostics")).clicked() {
+                        self.show_diagnostics = !self.show_diagnostics;
+                        self.need_to_save = true;
+                        ui.close_menu();
+                    }
+                    if ui.button(t!("bookmarks")).clicked() {
+                        self.show_bookmarks = !self.show_bookmarks;
+                        self.need_to_save = true;
+                        ui.close_menu();
+                    }
+                    if ui.button(t!("notes")).clicked() {
+                        self.show_notes = !self.show_notes;
+                        self.need_to_save = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    for (label, file_name, mime) in [
+                        (t!("export_png"), EXPORT_PNG_FILE, "image/png"),
+                        (t!("export_svg"), EXPORT_SVG_FILE, "image/svg+xml"),
+                    ] {
+                        if ui
+                            .add_enabled(self.grid.is_some(), Button::new(label))
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.export_map(ui.ctx(), file_name, mime);
+                        }
+                    }
                     // NOTE: no File->Quit on web pages
                     if !is_web {
                         ui.separator();
@@ -126,11 +319,18 @@ impl MarshrutkaApp {
                     self.show_about = !self.show_about;
                     self.need_to_save = true;
                 }
+                if let Some(status) = &self.export_status {
+                    ui.separator();
+                    status_label(ui, status);
+                    if ui.small_button("✖").clicked() {
+                        self.export_status = None;
+                    }
+                }
                 ui.separator();
                 ui.label(t!("your_homeland"));
                 let emoji_code = &self.homeland.into();
-                if let Some(flag) = self.emojis(ui.ctx()).get_texture(emoji_code) {
-                    if ImageButton::new(Image::new(&flag.corner).shrink_to_fit())
+                if let Some(flag) = self.emojis(ui.ctx()).texture(emoji_code, EMOJI_CORNER_SIZE) {
+                    if ImageButton::new(Image::new(&flag).shrink_to_fit())
                         .ui(ui)
                         .clicked()
                     {
@@ -167,6 +367,352 @@ impl MarshrutkaApp {
         });
     }
 
+    fn bookmarks(&mut self, ctx: &egui::Context) {
+        let mut show_bookmarks = self.show_bookmarks;
+        egui::Window::new(t!("bookmarks"))
+            .id(Id::new("bookmarks"))
+            .open(&mut show_bookmarks)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                let mut remove = None;
+                egui::Grid::new("bookmarks_grid").show(ui, |ui| {
+                    for (i, bookmark) in self.bookmarks.0.iter_mut().enumerate() {
+                        if ui.text_edit_singleline(&mut bookmark.name).changed() {
+                            self.need_to_save = true;
+                        }
+                        ui.label(bookmark.cell.to_string());
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = remove {
+                    self.bookmarks.0.remove(i);
+                    self.need_to_save = true;
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_bookmark.name)
+                            .hint_text(t!("bookmark_name"))
+                            .desired_width(CELL_INPUT_WIDTH * 2.0),
+                    );
+                    if let Some(grid) = &self.grid {
+                        let new_bookmark = &mut self.new_bookmark;
+                        if let Some(cell) = CellInput::new(
+                            "new_bookmark_cell",
+                            &mut new_bookmark.cell_text,
+                            new_bookmark.cell,
+                            grid,
+                        )
+                        .show(ui)
+                        {
+                            new_bookmark.cell = Some(cell);
+                        }
+                    }
+                    let name = self.new_bookmark.name.trim();
+                    if ui
+                        .add_enabled(
+                            !name.is_empty() && self.new_bookmark.cell.is_some(),
+                            Button::new(t!("add_bookmark")),
+                        )
+                        .clicked()
+                    {
+                        if let Some(cell) = self.new_bookmark.cell {
+                            self.bookmarks.insert(Bookmark {
+                                name: name.to_string(),
+                                cell,
+                            });
+                            self.new_bookmark = Default::default();
+                            self.need_to_save = true;
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(t!("copy_bookmarks")).clicked() {
+                        ui.ctx().copy_text(self.bookmarks.to_text());
+                    }
+                    // NOTE: no file dialogs on web pages, bookmarks are pasted below
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button(t!("import_bookmarks")).clicked() {
+                        if let Some(opened) = file_dialog::open_text(BOOKMARKS_FILE) {
+                            self.merge_bookmarks(opened);
+                        }
+                    }
+                    if ui.button(t!("export_bookmarks")).clicked() {
+                        if let Some(saved) = file_dialog::save(
+                            BOOKMARKS_FILE,
+                            "text/plain",
+                            self.bookmarks.to_text().as_bytes(),
+                        ) {
+                            self.bookmarks_status = Some(
+                                saved.map(|file| t!("bookmarks_exported", file = file).to_string()),
+                            );
+                        }
+                    }
+                });
+                if cfg!(target_arch = "wasm32") {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.bookmarks_import_text)
+                                .hint_text(t!("paste_bookmarks"))
+                                .desired_rows(2),
+                        );
+                        if ui
+                            .add_enabled(
+                                !self.bookmarks_import_text.trim().is_empty(),
+                                Button::new(t!("import_bookmarks")),
+                            )
+                            .clicked()
+                        {
+                            let text = std::mem::take(&mut self.bookmarks_import_text);
+                            self.merge_bookmarks(Ok((t!("pasted_text").to_string(), text)));
+                        }
+                    });
+                }
+                if let Some(status) = &self.bookmarks_status {
+                    status_label(ui, status);
+                }
+            });
+        if show_bookmarks != self.show_bookmarks {
+            self.show_bookmarks = show_bookmarks;
+            self.need_to_save = true;
+        }
+    }
+
+    fn notes(&mut self, ctx: &egui::Context) {
+        let mut show_notes = self.show_notes;
+        egui::Window::new(t!("notes"))
+            .id(Id::new("notes"))
+            .open(&mut show_notes)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(t!("cell"));
+                    if let Some(grid) = &self.grid {
+                        let note_edit = &mut self.note_edit;
+                        if let Some(cell) = CellInput::new(
+                            "note_cell",
+                            &mut note_edit.cell_text,
+                            note_edit.cell,
+                            grid,
+                        )
+                        .show(ui)
+                        {
+                            self.edit_note(cell);
+                        }
+                    }
+                });
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.note_edit.text)
+                        .hint_text(t!("note_text"))
+                        .desired_rows(2),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.note_edit.tags_text)
+                        .hint_text(t!("note_tags")),
+                );
+                if ui
+                    .add_enabled(self.note_edit.cell.is_some(), Button::new(t!("save_note")))
+                    .clicked()
+                {
+                    if let Some(cell) = self.note_edit.cell {
+                        let mut note = CellNote {
+                            text: self.note_edit.text.trim().to_string(),
+                            ..Default::default()
+                        };
+                        note.set_tags_text(&self.note_edit.tags_text);
+                        self.notes.set(cell, note);
+                        self.need_to_save = true;
+                    }
+                }
+
+                ui.separator();
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.notes_query).hint_text(t!("search_notes")),
+                );
+                let mut edit = None;
+                let mut remove = None;
+                egui::Grid::new("notes_grid").striped(true).show(ui, |ui| {
+                    for (&cell, note) in self.notes.search(&self.notes_query) {
+                        ui.label(cell.to_string());
+                        ui.label(&note.text);
+                        ui.weak(note.tags_text());
+                        if ui.small_button("✏").clicked() {
+                            edit = Some(cell);
+                        }
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(cell);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(cell) = edit {
+                    self.edit_note(cell);
+                }
+                if let Some(cell) = remove {
+                    self.notes.set(cell, CellNote::default());
+                    self.need_to_save = true;
+                }
+            });
+        if show_notes != self.show_notes {
+            self.show_notes = show_notes;
+            self.need_to_save = true;
+        }
+    }
+
+    /// Loads note of the cell into the notes window
+    fn edit_note(&mut self, cell: CellIndex) {
+        let note = self.notes.get(&cell).cloned().unwrap_or_default();
+        self.note_edit = NoteEdit {
+            cell: Some(cell),
+            cell_text: cell.to_string(),
+            text: note.text.clone(),
+            tags_text: note.tags_text(),
+        };
+    }
+
+    /// Toggles of tags, which highlight cells on the map
+    fn tag_filter(&mut self, ui: &mut Ui) {
+        let tags = self.notes.tags();
+        if tags.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label(t!("highlight_tags"));
+            for tag in tags {
+                let mut selected = self.highlight_tags.contains(tag);
+                if ui.toggle_value(&mut selected, tag).changed() {
+                    if selected {
+                        self.highlight_tags.insert(tag.to_string());
+                    } else {
+                        self.highlight_tags.remove(tag);
+                    }
+                    self.need_to_save = true;
+                }
+            }
+        });
+    }
+
+    /// Merges bookmarks from the text of `file`
+    fn merge_bookmarks(&mut self, opened: Result<(String, String), String>) {
+        let imported = opened.and_then(|(file, text)| {
+            Bookmarks::parse(&text)
+                .map(|imported| (file, imported))
+                .map_err(|err| err.to_string())
+        });
+        self.bookmarks_status = Some(imported.map(|(file, imported)| {
+            let count = imported.0.len();
+            self.bookmarks.merge(imported);
+            self.need_to_save = true;
+            t!("bookmarks_imported", count = count, file = file).to_string()
+        }));
+    }
+
+    /// Quick pick of bookmarks as route ends
+    fn bookmark_picker(&mut self, ui: &mut Ui) {
+        ui.menu_button("★", |ui| {
+            if self.bookmarks.is_empty() {
+                ui.weak(t!("no_bookmarks"));
+            }
+            egui::Grid::new("bookmark_picker").show(ui, |ui| {
+                for bookmark in &self.bookmarks.0 {
+                    let on_map = self
+                        .grid
+                        .as_ref()
+                        .is_some_and(|grid| grid.index.contains_key(&bookmark.cell));
+                    ui.label(&bookmark.name)
+                        .on_hover_text(bookmark.cell.to_string());
+                    if ui.add_enabled(on_map, Button::new(t!("from"))).clicked() {
+                        self.from = Some(bookmark.cell);
+                        self.need_to_save = true;
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(on_map, Button::new(t!("to"))).clicked() {
+                        self.to = Some(bookmark.cell);
+                        self.target_filter = None;
+                        self.need_to_save = true;
+                        ui.close_menu();
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            if ui.button(t!("manage_bookmarks")).clicked() {
+                self.show_bookmarks = true;
+                self.need_to_save = true;
+                ui.close_menu();
+            }
+        })
+        .response
+        .on_hover_text(t!("bookmarks"));
+    }
+
+    fn bookmark_markers(&self, painter: &Painter, centers: &HashMap<CellIndex, Pos2>) {
+        let zoom = self.map_view.zoom;
+        let font_id = FontId::proportional(BOOKMARK_MARKER_RADIUS * 1.5 * zoom);
+        for bookmark in &self.bookmarks.0 {
+            let Some(center) = centers.get(&bookmark.cell) else {
+                continue;
+            };
+            let pos = *center - Vec2::new(0.0, CELL_SIZE / 2.0 - BOOKMARK_MARKER_RADIUS) * zoom;
+            painter.circle_filled(pos, BOOKMARK_MARKER_RADIUS * zoom, Color32::GOLD);
+            painter.text(
+                pos,
+                Align2::CENTER_CENTER,
+                "★",
+                font_id.clone(),
+                Color32::BLACK,
+            );
+        }
+    }
+
+    /// Saves the map with the route as PNG or SVG, `file_name` is the default name
+    fn export_map(&mut self, ctx: &egui::Context, file_name: &str, mime: &str) {
+        let Some(grid) = &self.grid else {
+            return;
+        };
+        let mut export = MapExport::new(grid)
+            .theme(self.theme.clone())
+            .scale(ctx.pixels_per_point());
+        if let Some(path) = &self.path {
+            export = export.commands(&path.commands);
+        }
+        // Format is given by the file, which the user chooses
+        let saved = file_dialog::save_as(file_name, mime, |extension| {
+            export.encode(extension).map_err(|err| err.to_string())
+        });
+        if let Some(saved) = saved {
+            self.export_status = Some(saved.map(|file| t!("exported_to", file = file).to_string()));
+        }
+    }
+
+    /// Saves the schedule as a calendar file
+    fn export_schedule(&mut self, path: &TotalCost) {
+        let events = self
+            .schedule(path)
+            .into_iter()
+            .filter_map(|(command, start)| {
+                Some(CalendarEvent {
+                    summary: command.text()?,
+                    description: format!("{} → {}", command.from, command.to),
+                    start,
+                    duration: command.aggregated_cost.time(),
+                })
+            })
+            .collect();
+        let ics = Calendar::new(events)
+            .reminder(Duration::minutes(self.calendar_reminder as i64))
+            .to_ics(OffsetDateTime::now_utc());
+        if let Some(saved) = file_dialog::save(SCHEDULE_ICS_FILE, "text/calendar", ics.as_bytes()) {
+            self.export_status = Some(saved.map(|file| t!("exported_to", file = file).to_string()));
+        }
+    }
+
     fn about(&mut self, ctx: &egui::Context) {
         egui::Window::new(t!("about"))
             .id(Id::new("about"))
@@ -201,6 +747,7 @@ impl MarshrutkaApp {
     }
 
     fn settings(&mut self, ctx: &egui::Context) {
+        let mut server_offset = None;
         egui::Window::new(t!("settings"))
             .id(Id::new("settings"))
             .open(&mut self.show_settings)
@@ -240,6 +787,9 @@ impl MarshrutkaApp {
                                 }
                             }
                         });
+                    if self.theme.settings_ui(ui) {
+                        self.need_to_save = true;
+                    }
                     ui.horizontal(|ui| {
                         if egui::DragValue::new(&mut self.route_guru_skill)
                             .clamp_existing_to_range(true)
@@ -363,6 +913,38 @@ impl MarshrutkaApp {
                         }
                         ui.label(t!("pause_between_steps"));
                     });
+                    ui.horizontal(|ui| {
+                        let mut minutes = self.server_utc_offset.whole_minutes();
+                        if egui::DragValue::new(&mut minutes)
+                            .custom_formatter(|n, _| {
+                                let n = n as i16;
+                                let sign = if n < 0 { '-' } else { '+' };
+                                format!("UTC{sign}{:02}:{:02}", n.abs() / 60, n.abs() % 60)
+                            })
+                            .speed(15.0)
+                            .range(-12 * 60..=14 * 60)
+                            .ui(ui)
+                            .changed()
+                        {
+                            // Offsets are multiples of a quarter of an hour
+                            let minutes = minutes / 15 * 15;
+                            server_offset =
+                                UtcOffset::from_hms((minutes / 60) as i8, (minutes % 60) as i8, 0)
+                                    .ok();
+                        }
+                        ui.label(t!("server_utc_offset"));
+                    });
+                    ui.horizontal(|ui| {
+                        if egui::DragValue::new(&mut self.calendar_reminder)
+                            .clamp_existing_to_range(true)
+                            .range(0..=Minute::per(Hour) as u32)
+                            .ui(ui)
+                            .changed()
+                        {
+                            self.need_to_save = true;
+                        }
+                        ui.label(t!("calendar_reminder"));
+                    });
                     ui.horizontal(|ui| {
                         ui.scope(|ui| {
                             ui.spacing_mut().item_spacing.x = 2.0;
@@ -380,6 +962,47 @@ impl MarshrutkaApp {
                         });
                         ui.label(t!("map_url"));
                     });
+                    if !cfg!(target_arch = "wasm32") {
+                        ui.horizontal(|ui| {
+                            if egui::TextEdit::singleline(&mut self.emoji_dir)
+                                .desired_width(160.0)
+                                .ui(ui)
+                                .on_hover_text(t!("emoji_dir_hint"))
+                                .changed()
+                            {
+                                // Reload emojis from the new directory
+                                self.emojis = OnceCell::new();
+                                self.need_to_save = true;
+                            }
+                            ui.label(t!("emoji_dir"));
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        if egui::DragValue::new(&mut self.map_refresh_interval)
+                            .clamp_existing_to_range(true)
+                            .range(0..=Minute::per(Day) as u32)
+                            .ui(ui)
+                            .changed()
+                        {
+                            self.need_to_save = true;
+                        }
+                        ui.label(t!("map_refresh_interval"));
+                    });
+                    ui.horizontal(|ui| {
+                        if egui::DragValue::new(&mut self.map_history_size)
+                            .clamp_existing_to_range(true)
+                            .range(0..=100)
+                            .ui(ui)
+                            .changed()
+                        {
+                            if self.map_history.truncate(self.map_history_size) {
+                                self.history_view = None;
+                                self.need_to_save_history = true;
+                            }
+                            self.need_to_save = true;
+                        }
+                        ui.label(t!("map_history_size"));
+                    });
                     if ui
                         .checkbox(&mut self.command_via_chat_link, t!("use_direct_chat_link"))
                         .on_hover_text(t!("use_with_caution_at_least_on_android"))
@@ -387,8 +1010,55 @@ impl MarshrutkaApp {
                     {
                         self.need_to_save = true;
                     }
+                    if ui
+                        .checkbox(&mut self.alarm_sound, t!("alarm_sound"))
+                        .changed()
+                    {
+                        self.need_to_save = true;
+                    }
+                    if !cfg!(target_arch = "wasm32")
+                        && ui
+                            .checkbox(&mut self.alarm_notification, t!("alarm_notification"))
+                            .changed()
+                    {
+                        self.need_to_save = true;
+                    }
                 });
             });
+        if let Some(server_offset) = server_offset {
+            self.set_clock(Clock {
+                server_offset,
+                ..self.clock()
+            });
+        }
+    }
+
+    fn diagnostics(&mut self, ctx: &egui::Context) {
+        let Some(grid) = &self.grid else {
+            return;
+        };
+        egui::Window::new(t!("diagnostics"))
+            .id(Id::new("diagnostics"))
+            .open(&mut self.show_diagnostics)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                if grid.errors.is_empty() && self.map_issues.is_empty() {
+                    ui.label(t!("map_is_valid"));
+                    return;
+                }
+                if !grid.errors.is_empty() {
+                    ui.heading(t!("parse_errors"));
+                    for error in &grid.errors {
+                        ui.colored_label(ui.visuals().warn_fg_color, error.to_string());
+                    }
+                }
+                if !self.map_issues.is_empty() {
+                    ui.heading(t!("map_issues"));
+                    for issue in &self.map_issues {
+                        ui.colored_label(ui.visuals().warn_fg_color, issue.to_string());
+                    }
+                }
+            });
     }
 
     fn commands(&mut self, ctx: &egui::Context) {
@@ -410,137 +1080,171 @@ impl MarshrutkaApp {
                             let path = path.clone();
                             ScrollArea::horizontal().show(ui, |ui| {
                                 ui.horizontal(|ui| {
-                                    fn show_item(
-                                        s: &MarshrutkaApp,
-                                        ui: &mut Ui,
-                                        ch: char,
-                                        val: impl Display,
-                                    ) {
-                                        ui.scope(|ui| {
-                                            ui.spacing_mut().item_spacing.x = 4.0;
-                                            ui.label(val.to_string());
-                                            Image::new(
-                                                &s.emojis(ui.ctx())
-                                                    .get_texture(&ch.into())
-                                                    .unwrap()
-                                                    .corner,
+                                    self.show_cost(ui, &path);
+
+                                    for mode in ScheduleMode::iter() {
+                                        let name: &'static str = mode.into();
+                                        if ui
+                                            .selectable_value(
+                                                &mut self.schedule_mode,
+                                                mode,
+                                                t!(name),
                                             )
-                                            .max_height(ui.text_style_height(&TextStyle::Body))
-                                            .ui(ui);
-                                        });
+                                            .changed()
+                                        {
+                                            self.need_to_save = true;
+                                        }
                                     }
-                                    show_item(self, ui, '\u{1f463}', path.legs);
-                                    show_item(self, ui, '\u{23f0}', path.time);
-                                    show_item(self, ui, '\u{1fa99}', path.money);
-
-                                    ui.label(t!("arrive_at"));
                                     ui.scope(|ui| {
                                         ui.spacing_mut().item_spacing.x = 1.0;
                                         let formatter = |n, _| {
                                             let n = n as u32;
                                             format!("{n:02}")
                                         };
-                                        let mut hr = self.arrive_at.hour();
+                                        let (mut year, month, mut day) =
+                                            self.schedule_date.to_calendar_date();
+                                        let mut month = month as u8;
+                                        let year_changed = egui::DragValue::new(&mut year)
+                                            .range(2000..=9999)
+                                            .ui(ui)
+                                            .changed();
+                                        ui.label("-");
+                                        let month_changed = egui::DragValue::new(&mut month)
+                                            .custom_formatter(formatter)
+                                            .range(1..=12)
+                                            .ui(ui)
+                                            .changed();
+                                        ui.label("-");
+                                        let day_changed = egui::DragValue::new(&mut day)
+                                            .custom_formatter(formatter)
+                                            .range(1..=31)
+                                            .ui(ui)
+                                            .changed();
+                                        if year_changed || month_changed || day_changed {
+                                            let month = month.try_into().unwrap();
+                                            let day =
+                                                day.min(time::util::days_in_month(month, year));
+                                            if let Ok(date) =
+                                                Date::from_calendar_date(year, month, day)
+                                            {
+                                                self.schedule_date = date;
+                                                self.need_to_save = true;
+                                            }
+                                        }
+                                        ui.add_space(4.0);
+                                        let mut hr = self.schedule_time.hour();
                                         if egui::DragValue::new(&mut hr)
                                             .custom_formatter(formatter)
                                             .range(0..=23)
                                             .ui(ui)
                                             .changed()
                                         {
-                                            self.arrive_at =
-                                                self.arrive_at.replace_hour(hr).unwrap();
+                                            self.schedule_time =
+                                                self.schedule_time.replace_hour(hr).unwrap();
                                             self.need_to_save = true;
                                         }
                                         ui.label(":");
-                                        let mut mi = self.arrive_at.minute();
+                                        let mut mi = self.schedule_time.minute();
                                         if egui::DragValue::new(&mut mi)
                                             .custom_formatter(formatter)
                                             .range(0..=59)
                                             .ui(ui)
                                             .changed()
                                         {
-                                            self.arrive_at =
-                                                self.arrive_at.replace_minute(mi).unwrap();
+                                            self.schedule_time =
+                                                self.schedule_time.replace_minute(mi).unwrap();
                                             self.need_to_save = true;
                                         }
                                         ui.label(":");
-                                        let mut sec = self.arrive_at.second();
+                                        let mut sec = self.schedule_time.second();
                                         if egui::DragValue::new(&mut sec)
                                             .custom_formatter(formatter)
                                             .range(0..=59)
                                             .ui(ui)
                                             .changed()
                                         {
-                                            self.arrive_at =
-                                                self.arrive_at.replace_second(sec).unwrap();
+                                            self.schedule_time =
+                                                self.schedule_time.replace_second(sec).unwrap();
                                             self.need_to_save = true;
                                         }
                                     });
-                                });
-
-                                egui::Grid::new("commands_grid")
-                                    .striped(true)
-                                    .show(ui, |ui| {
-                                        ui.label(t!("command"));
+                                    if self.schedule_mode == ScheduleMode::DepartAt {
+                                        let arrival = self
+                                            .clock()
+                                            .format_time(self.arrival(&path), self.schedule_date);
+                                        ui.label(t!("arrival_time", time = arrival));
+                                    }
+                                    egui::ComboBox::from_id_salt("clock_zone")
+                                        .selected_text(t!(self.clock_zone.name()))
+                                        .show_ui(ui, |ui| {
+                                            for zone in ClockZone::iter() {
+                                                if ui
+                                                    .selectable_label(
+                                                        self.clock_zone == zone,
+                                                        t!(zone.name()),
+                                                    )
+                                                    .clicked()
+                                                {
+                                                    self.set_clock(Clock {
+                                                        zone,
+                                                        ..self.clock()
+                                                    });
+                                                }
+                                            }
+                                        })
+                                        .response
+                                        .on_hover_text(t!("clock_zone"));
+                                    if ui.button("📌").on_hover_text(t!("pin_route")).clicked() {
+                                        self.pin_route();
+                                    }
+                                    if ui
+                                        .button("📅")
+                                        .on_hover_text(t!("export_schedule"))
+                                        .clicked()
+                                    {
+                                        self.export_schedule(&path);
+                                    }
+                                    if ui
+                                        .add_enabled(self.trip.is_none(), Button::new("\u{1f6b6}"))
+                                        .on_hover_text(t!("start_trip"))
+                                        .clicked()
+                                    {
+                                        self.start_trip(&path);
+                                    }
+                                    if self.trip_timer.is_some() {
+                                        if ui
+                                            .button("\u{23f9}")
+                                            .on_hover_text(t!("stop_trip_timer"))
+                                            .clicked()
+                                        {
+                                            self.trip_timer = None;
+                                            self.alarm = None;
+                                        }
+                                    } else if ui
+                                        .button("\u{23f1}")
+                                        .on_hover_text(t!("start_trip_timer"))
+                                        .clicked()
+                                    {
+                                        self.start_trip_timer(&path);
+                                    }
+                                });
+                                self.trip_timer_status(ui);
+
+                                egui::Grid::new("commands_grid")
+                                    .striped(true)
+                                    .show(ui, |ui| {
+                                        ui.label(t!("command"));
                                         ui.label(t!("duration"));
                                         ui.label(t!("total_time"));
                                         ui.label(t!("schedule_at"));
                                         ui.end_row();
                                         let mut total_time = Duration::ZERO;
-                                        let commands: SmallVec<[_; 5]> = path
-                                            .commands
-                                            .iter()
-                                            .filter(|command| {
-                                                !matches!(
-                                                    command,
-                                                    Command {
-                                                        aggregated_cost: AggregatedCost::NoMove,
-                                                        ..
-                                                    }
-                                                )
-                                            })
-                                            .copied()
-                                            .collect();
+                                        let clock = self.clock();
                                         let pause_between_steps =
                                             Duration::seconds(self.pause_between_steps as i64);
-                                        let times: SmallVec<[_; 5]> = commands
-                                            .iter()
-                                            .rev()
-                                            .scan(self.arrive_at, |acc, command| {
-                                                *acc -= command.aggregated_cost.time()
-                                                    + pause_between_steps;
-                                                Some(*acc)
-                                            })
-                                            .collect();
-                                        let time_format =
-                                            format_description!("[hour]:[minute]:[second]");
-                                        for (command, time) in
-                                            iter::zip(commands, times.into_iter().rev())
-                                        {
-                                            let command_str = match command.aggregated_cost {
-                                                AggregatedCost::NoMove => continue,
-                                                AggregatedCost::CentralMove { .. }
-                                                | AggregatedCost::StandardMove { .. } => {
-                                                    format!(
-                                                        "/go_direct_{}",
-                                                        CellIndexCommandSuffix(command.to)
-                                                    )
-                                                }
-                                                AggregatedCost::Caravan(_) => {
-                                                    format!(
-                                                        "/car_{}",
-                                                        CellIndexCommandSuffix(command.to)
-                                                    )
-                                                }
-                                                AggregatedCost::ScrollOfEscape { .. } => {
-                                                    "/use_soe".to_string()
-                                                }
-                                                AggregatedCost::ScrollOfEscapeHQ { .. } => {
-                                                    "/use_shq".to_string()
-                                                }
-                                                AggregatedCost::ScrollOfEscapeForum { .. } => {
-                                                    "/use_sfm".to_string()
-                                                }
+                                        for (command, time) in self.schedule(&path) {
+                                            let Some(command_str) = command.text() else {
+                                                continue;
                                             };
                                             egui::Hyperlink::from_label_and_url(
                                                 &command_str,
@@ -553,10 +1257,10 @@ impl MarshrutkaApp {
                                             .open_in_new_tab(true)
                                             .ui(ui);
                                             let command_time = command.aggregated_cost.time();
-                                            ui.label(command_time.to_string());
+                                            ui.label(format_duration(command_time));
                                             total_time += command_time + pause_between_steps;
-                                            ui.label(total_time.to_string());
-                                            ui.label(time.format(&time_format).unwrap());
+                                            ui.label(format_duration(total_time));
+                                            ui.label(clock.format_time(time, self.schedule_date));
                                             ui.end_row();
                                         }
                                     });
@@ -564,95 +1268,656 @@ impl MarshrutkaApp {
                         }
                     }
                 });
+            if !self.pinned_routes.is_empty() {
+                egui::CollapsingHeader::new(t!("pinned_routes"))
+                    .id_salt("pinned_routes_header")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ScrollArea::horizontal().show(ui, |ui| self.pinned_routes_table(ui));
+                    });
+            }
+        });
+    }
+
+    fn pin_route(&mut self) {
+        let options = self.route_options();
+        if self
+            .pinned_routes
+            .iter()
+            .any(|route| route.options == options)
+        {
+            return;
+        }
+        self.pinned_routes.push(PinnedRoute {
+            name: options.to_string(),
+            options,
         });
+        self.need_to_save = true;
+    }
+
+    fn pinned_routes_table(&mut self, ui: &mut Ui) {
+        let clock = self.clock();
+        let mut remove = None;
+        // Command count and departure time of each route
+        let schedules: Vec<_> = self
+            .pinned_paths
+            .iter()
+            .map(|path| {
+                path.as_ref().map(|path| {
+                    let schedule = self.schedule(path);
+                    (schedule.len(), schedule.first().map(|&(_, time)| time))
+                })
+            })
+            .collect();
+        egui::Grid::new("pinned_routes_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label(t!("route_name"));
+                ui.label(t!("Legs"));
+                ui.label(t!("Time"));
+                ui.label(t!("Money"));
+                ui.label(t!("commands"));
+                ui.label(t!("departure"));
+                ui.label(t!("scrolls"));
+                ui.end_row();
+                for (i, ((route, path), schedule)) in
+                    iter::zip(&mut self.pinned_routes, &self.pinned_paths)
+                        .zip(&schedules)
+                        .enumerate()
+                {
+                    let (rect, _) = ui.allocate_exact_size(
+                        Vec2::new(ARROW_LEGEND_WIDTH, ARROW_WIDTH),
+                        Sense::hover(),
+                    );
+                    let color = pinned_route_color(i, ui.visuals().dark_mode);
+                    ui.painter().rect_filled(rect, 0.0, color);
+                    if ui.text_edit_singleline(&mut route.name).changed() {
+                        self.need_to_save = true;
+                    }
+                    match path.as_ref().zip(*schedule) {
+                        Some((path, (command_count, departure))) => {
+                            ui.label(path.legs.to_string());
+                            ui.label(format_duration(path.time));
+                            ui.label(path.money.to_string());
+                            ui.label(command_count.to_string());
+                            ui.label(
+                                departure
+                                    .map(|time| clock.format_time(time, self.schedule_date))
+                                    .unwrap_or_default(),
+                            );
+                            ui.label(scrolls_used(path));
+                        }
+                        None => {
+                            ui.label(t!("no_route"));
+                            for _ in 0..5 {
+                                ui.label("");
+                            }
+                        }
+                    }
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            self.pinned_routes.remove(i);
+            self.pinned_paths.remove(i);
+            self.need_to_save = true;
+        }
     }
 
     fn central_panel(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label(t!(
-                    "from_to",
-                    from = self.from.map(|s| s.to_string()).unwrap_or_default(),
-                    to = self.to.map(|s| s.to_string()).unwrap_or_default()
-                ));
-                if ui.checkbox(&mut self.use_soe, "SoE").changed() {
-                    self.need_to_save = true;
-                }
-                if ui.checkbox(&mut self.use_shq, "SHQ").changed() {
-                    self.need_to_save = true;
-                }
-                if ui.checkbox(&mut self.use_sfm, "SFm").changed() {
+                let grid = self.grid.as_ref().unwrap();
+                ui.label(t!("from"));
+                if let Some(from) =
+                    CellInput::new("from_input", &mut self.from_text, self.from, grid).show(ui)
+                {
+                    self.from = Some(from);
                     self.need_to_save = true;
                 }
-                if ui
-                    .checkbox(&mut self.use_caravans, t!("caravans"))
-                    .changed()
+                ui.label(t!("to"));
+                if let Some(to) =
+                    CellInput::new("to_input", &mut self.to_text, self.to, grid).show(ui)
                 {
+                    self.to = Some(to);
+                    self.target_filter = None;
                     self.need_to_save = true;
                 }
+                self.bookmark_picker(ui);
+                let caravans = t!("caravans");
+                for (flag, label, shortcut) in [
+                    (&mut self.use_soe, "SoE", TOGGLE_SOE_SHORTCUT),
+                    (&mut self.use_shq, "SHQ", TOGGLE_SHQ_SHORTCUT),
+                    (&mut self.use_sfm, "SFm", TOGGLE_SFM_SHORTCUT),
+                    (&mut self.use_caravans, &caravans, TOGGLE_CARAVANS_SHORTCUT),
+                ] {
+                    if ui.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                        *flag = !*flag;
+                        self.need_to_save = true;
+                    }
+                    if ui
+                        .checkbox(flag, label)
+                        .on_hover_text(ui.ctx().format_shortcut(&shortcut))
+                        .changed()
+                    {
+                        self.need_to_save = true;
+                    }
+                }
+                ui.separator();
+                self.target_filter_picker(ui);
             });
 
+            if let Some(notice) = self.map_update_notice {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        t!("map_updated", count = notice.changed_cells),
+                    );
+                    if notice.route_affected {
+                        ui.colored_label(ui.visuals().error_fg_color, t!("route_affected"));
+                    }
+                    if ui.small_button("✖").clicked() {
+                        self.map_update_notice = None;
+                    }
+                });
+            }
+
             ui.separator();
 
             let grid_response = egui::CollapsingHeader::new(t!("map"))
                 .id_salt("map")
                 .default_open(true)
                 .show(ui, |ui| {
+                    self.timeline(ui);
+                    ui.small(t!("hint", help1 = t!("help1"), help2 = t!("help2")));
+                    ui.small(t!("map_zoom_hint"));
+                    ui.small(t!("map_keyboard_hint"));
+                    if self.path.is_some() {
+                        self.route_legend(ui);
+                    }
+                    self.tag_filter(ui);
+                    let grid = self
+                        .history_view
+                        .as_ref()
+                        .map_or(self.grid.as_ref().unwrap(), |view| &view.grid);
+                    if !grid.errors.is_empty() {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            t!("invalid_cells", count = grid.errors.len()),
+                        )
+                        .on_hover_text(
+                            grid.errors
+                                .iter()
+                                .map(|e| e.to_string())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        );
+                    }
+                    // Later highlights win
+                    let highlights = self
+                        .notes
+                        .tagged(&self.highlight_tags)
+                        .map(|cell_index| (cell_index, NOTE_BADGE_COLOR))
+                        .chain(
+                            self.history_view
+                                .iter()
+                                .flat_map(|view| view.changed_cells.iter())
+                                .map(|&cell_index| (cell_index, Color32::LIGHT_BLUE)),
+                        )
+                        .chain(
+                            self.changed_cells
+                                .iter()
+                                .map(|&cell_index| (cell_index, Color32::GOLD)),
+                        );
+                    let mut marks: HashMap<_, CellMarks> = HashMap::new();
+                    for (cell_index, color) in highlights {
+                        marks.entry(cell_index).or_default().highlight = Some(color);
+                    }
+                    for (&cell_index, _) in self.notes.iter() {
+                        marks.entry(cell_index).or_default().note = true;
+                    }
+                    let emojis = self
+                        .emojis
+                        .get_or_init(|| new_emoji_map(ui.ctx(), &self.emoji_dir));
                     let InnerResponse {
                         inner:
                             MapGridResponse {
                                 centers,
                                 left: from,
                                 right: to,
+                                hovered,
                             },
-                        response,
-                    } = ScrollArea::both()
-                        .show(ui, |ui| {
-                            ui.small(t!("hint", help1 = t!("help1"), help2 = t!("help2")));
-                            let emojis = self.emojis(ui.ctx());
-                            self.grid.as_ref().unwrap().ui_content(ui, emojis)
-                        })
-                        .inner;
-                    if let Some(from) = from {
+                        mut response,
+                    } = grid.ui_content(ui, emojis, &marks, &mut self.map_view);
+                    if let Some(hovered) = hovered {
+                        self.update_hover_cost(hovered);
+                        response = response.on_hover_ui(|ui| self.cell_tooltip(ui, hovered));
+                    }
+                    // Snapshot may have cells, which are absent in the live map
+                    let is_live = |cell_index: &CellIndex| {
+                        self.grid
+                            .as_ref()
+                            .is_some_and(|grid| grid.index.contains_key(cell_index))
+                    };
+                    if let Some(from) = from.filter(is_live) {
                         self.from = Some(from);
                         self.need_to_save = true;
                     }
-                    if let Some(to) = to {
+                    if let Some(to) = to.filter(is_live) {
                         self.to = Some(to);
+                        self.target_filter = None;
                         self.need_to_save = true;
                     }
                     (centers, response)
                 })
                 .body_returned;
-            if let Some((path, (centers, grid_response))) = self.path.as_ref().zip(grid_response) {
-                let painter = ui.painter_at(grid_response.interact_rect);
-                let rot = Rot2::from_angle(std::f32::consts::TAU / 10.0);
-                let tip_length = CELL_SIZE / 4.0;
-                for command in path.commands.iter() {
-                    arrow(
-                        &painter,
-                        rot,
-                        tip_length,
-                        centers[&command.from],
-                        centers[&command.to],
-                        match command.aggregated_cost {
-                            AggregatedCost::NoMove => continue,
-                            AggregatedCost::CentralMove { .. } => Color32::RED,
-                            AggregatedCost::StandardMove { .. } => Color32::BLUE,
-                            AggregatedCost::Caravan(_) => Color32::DARK_GREEN,
-                            AggregatedCost::ScrollOfEscape { .. } => Color32::BROWN,
-                            AggregatedCost::ScrollOfEscapeHQ { .. } => Color32::WHITE,
-                            AggregatedCost::ScrollOfEscapeForum { .. } => Color32::PURPLE,
-                        }
-                        .gamma_multiply(BLEACH_ALPHA as f32 / 255.0),
+            let Some((centers, grid_response)) = grid_response else {
+                return;
+            };
+            let painter = ui.painter_at(grid_response.interact_rect);
+            self.bookmark_markers(&painter, &centers);
+            if let Some(path) = &self.path {
+                let pinned_paths = self
+                    .pinned_paths
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, path)| {
+                        let color = pinned_route_color(i, ui.visuals().dark_mode);
+                        Some((path.as_deref()?, Some(color)))
+                    });
+                let routes: Vec<_> = pinned_paths.chain(iter::once((&**path, None))).collect();
+                self.route_overlay(ui, &painter, &routes, &centers);
+            }
+        });
+    }
+
+    fn route_overlay(
+        &self,
+        ui: &Ui,
+        painter: &Painter,
+        routes: &[(&TotalCost, Option<Color32>)],
+        centers: &HashMap<CellIndex, Pos2>,
+    ) {
+        let rot = Rot2::from_angle(ARROW_TIP_ANGLE);
+        let tip_length = CELL_SIZE / 4.0 * self.map_view.zoom;
+        let clock = self.clock();
+        let font_id = TextStyle::Small.resolve(ui.style());
+        let mut lanes = ArrowLanes::default();
+        // Pinned routes are drawn in their own colour, without step labels
+        let steps = routes.iter().flat_map(|&(path, route_color)| {
+            self.schedule(path)
+                .into_iter()
+                .enumerate()
+                .map(move |(step, scheduled)| (step, scheduled, route_color))
+        });
+        for (step, (command, time), route_color) in steps {
+            let Some((kind, from, to)) = lanes.take(&command, centers, self.map_view.zoom) else {
+                continue;
+            };
+            let color = route_color.unwrap_or(self.theme.move_color(kind, ui.visuals().dark_mode));
+            arrow(
+                painter,
+                rot,
+                tip_length,
+                from,
+                to,
+                color.gamma_multiply(BLEACH_ALPHA as f32 / 255.0),
+            );
+            if route_color.is_some() {
+                continue;
+            }
+
+            let galley = painter.layout_no_wrap(
+                format!(
+                    "{}. {}",
+                    step + 1,
+                    clock.format_time(time, self.schedule_date)
+                ),
+                font_id.clone(),
+                label_text_color(color),
+            );
+            let label_rect = Align2::CENTER_CENTER
+                .anchor_size(from.lerp(to, 0.5), galley.size())
+                .expand(2.0);
+            painter.rect_filled(label_rect, 2.0, color.gamma_multiply(0.8));
+            painter.galley(label_rect.shrink(2.0).min, galley, label_text_color(color));
+        }
+    }
+
+    fn route_legend(&self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for kind in MoveKind::iter() {
+                let (rect, _) = ui.allocate_exact_size(
+                    Vec2::new(ARROW_LEGEND_WIDTH, ARROW_WIDTH),
+                    Sense::hover(),
+                );
+                ui.painter().rect_filled(
+                    rect,
+                    0.0,
+                    self.theme.move_color(kind, ui.visuals().dark_mode),
+                );
+                ui.label(t!(kind.name()));
+            }
+        });
+    }
+
+    /// Moving commands of the route with their departure times
+    fn schedule(&self, path: &TotalCost) -> SmallVec<[(Command, OffsetDateTime); 5]> {
+        match self.schedule_mode {
+            ScheduleMode::ArriveAt => self.schedule_to(path, self.schedule_anchor()),
+            ScheduleMode::DepartAt => self.schedule_from(path, self.schedule_anchor()),
+        }
+    }
+
+    fn clock(&self) -> Clock {
+        Clock {
+            zone: self.clock_zone,
+            server_offset: self.server_utc_offset,
+        }
+    }
+
+    /// Changes time zone, keeping the moment of arrival or departure
+    fn set_clock(&mut self, clock: Clock) {
+        let anchor = clock.to_zone(self.schedule_anchor());
+        self.clock_zone = clock.zone;
+        self.server_utc_offset = clock.server_offset;
+        self.schedule_date = anchor.date();
+        self.schedule_time = anchor.time();
+        self.need_to_save = true;
+    }
+
+    /// Arrival or departure time as it is entered
+    fn schedule_anchor(&self) -> OffsetDateTime {
+        self.clock().assume(PrimitiveDateTime::new(
+            self.schedule_date,
+            self.schedule_time,
+        ))
+    }
+
+    fn arrival(&self, path: &TotalCost) -> OffsetDateTime {
+        match self.schedule_mode {
+            ScheduleMode::ArriveAt => self.schedule_anchor(),
+            ScheduleMode::DepartAt => self.schedule_anchor() + self.travel_time(path),
+        }
+    }
+
+    /// Time of all moving commands with pauses between them
+    fn travel_time(&self, path: &TotalCost) -> Duration {
+        let pause_between_steps = Duration::seconds(self.pause_between_steps as i64);
+        path.commands
+            .iter()
+            .filter(|command| command.aggregated_cost.kind().is_some())
+            .map(|command| command.aggregated_cost.time() + pause_between_steps)
+            .sum()
+    }
+
+    /// Moving commands of the route, which departs at the given time
+    fn schedule_from(
+        &self,
+        path: &TotalCost,
+        depart_at: OffsetDateTime,
+    ) -> SmallVec<[(Command, OffsetDateTime); 5]> {
+        let pause_between_steps = Duration::seconds(self.pause_between_steps as i64);
+        path.commands
+            .iter()
+            .filter(|command| command.aggregated_cost.kind().is_some())
+            .scan(depart_at, |acc, command| {
+                let departure = *acc;
+                *acc += command.aggregated_cost.time() + pause_between_steps;
+                Some((*command, departure))
+            })
+            .collect()
+    }
+
+    /// Moving commands of the route, which arrives at the given time
+    fn schedule_to(
+        &self,
+        path: &TotalCost,
+        arrive_at: OffsetDateTime,
+    ) -> SmallVec<[(Command, OffsetDateTime); 5]> {
+        let pause_between_steps = Duration::seconds(self.pause_between_steps as i64);
+        let mut schedule: SmallVec<[_; 5]> = path
+            .commands
+            .iter()
+            .rev()
+            .filter(|command| command.aggregated_cost.kind().is_some())
+            .scan(arrive_at, |acc, command| {
+                *acc -= command.aggregated_cost.time() + pause_between_steps;
+                Some((*command, *acc))
+            })
+            .collect();
+        schedule.reverse();
+        schedule
+    }
+
+    fn show_cost(&self, ui: &mut Ui, cost: &TotalCost) {
+        fn show_item(s: &MarshrutkaApp, ui: &mut Ui, ch: char, val: impl Display) {
+            ui.scope(|ui| {
+                ui.spacing_mut().item_spacing.x = 4.0;
+                ui.label(val.to_string());
+                let height = ui.text_style_height(&TextStyle::Body);
+                let texture = s.emojis(ui.ctx()).texture(&ch.into(), height).unwrap();
+                Image::new(&texture).max_height(height).ui(ui);
+            });
+        }
+        show_item(self, ui, '\u{1f463}', cost.legs);
+        show_item(self, ui, '\u{23f0}', format_duration(cost.time));
+        show_item(self, ui, '\u{1fa99}', cost.money);
+    }
+
+    fn update_hover_cost(&mut self, hovered: CellIndex) {
+        if self
+            .hover_cost
+            .as_ref()
+            .is_some_and(|(to, _)| *to == hovered)
+        {
+            return;
+        }
+        let cost = self
+            .from
+            .zip(self.find_path())
+            .filter(|(_, find_path)| find_path.grid.index.contains_key(&hovered))
+            .and_then(|(from, find_path)| find_path.eval(from, hovered));
+        self.hover_cost = Some((hovered, cost));
+    }
+
+    fn cell_tooltip(&self, ui: &mut Ui, cell_index: CellIndex) {
+        let grid = self
+            .history_view
+            .as_ref()
+            .map_or(self.grid.as_ref(), |view| Some(&view.grid));
+        let Some(cell) = grid.and_then(|grid| grid.index.get(&cell_index).map(|&i| &grid.grid[i]))
+        else {
+            return;
+        };
+        let emojis = self.emojis(ui.ctx());
+        let emoji_height = ui.text_style_height(&TextStyle::Body);
+        ui.strong(cell_index.to_string());
+        for bookmark in self.bookmarks.at(cell_index) {
+            ui.label(format!("★ {}", bookmark.name));
+        }
+        if let Some(note) = self.notes.get(&cell_index) {
+            ui.label(format!("📝 {}", note.text));
+            if !note.tags.is_empty() {
+                ui.weak(note.tags_text());
+            }
+        }
+        ui.label(t!("coordinates", x = cell.x, y = cell.y));
+        ui.monospace(format!("/go_direct_{}", CellIndexCommandSuffix(cell_index)));
+        if cell.invalid {
+            ui.colored_label(ui.visuals().warn_fg_color, t!("invalid_cell"));
+        }
+        ui.separator();
+        egui::Grid::new("cell_tooltip_elements").show(ui, |ui| {
+            for (name, cell_element) in [
+                ("top_left", &cell.top_left),
+                ("top_right", &cell.top_right),
+                ("bottom_left", &cell.bottom_left),
+                ("bottom_right", &cell.bottom_right),
+            ] {
+                ui.label(t!(name));
+                match cell_element {
+                    Some(CellElement::Emoji(emoji_code)) => {
+                        match emojis.texture(emoji_code, emoji_height) {
+                            Some(texture) => {
+                                Image::new(&texture).max_height(emoji_height).ui(ui);
+                            }
+                            None => {
+                                ui.label(emoji_code.to_string());
+                            }
+                        }
+                    }
+                    Some(CellElement::Text(text)) => {
+                        ui.label(text);
+                    }
+                    None => {
+                        ui.weak("—");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(poi) = cell.contents.poi() {
+            ui.label(t!("point_of_interest", poi = t!(poi.name())));
+        }
+        if let Some(campfire) = cell
+            .nearest_campfire
+            .get()
+            .and_then(|nearest_campfire| nearest_campfire[self.homeland])
+        {
+            ui.label(t!("nearest_campfire", campfire = campfire.to_string()));
+        }
+        if let Some((_, Some(cost))) = &self.hover_cost {
+            ui.separator();
+            ui.label(t!(
+                "from_cell",
+                from = self.from.map(|from| from.to_string()).unwrap_or_default()
+            ));
+            ui.horizontal(|ui| self.show_cost(ui, cost));
+        }
+    }
+
+    fn timeline(&mut self, ui: &mut Ui) {
+        if self.map_history.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label(t!("history"));
+            let live = self.map_history.len();
+            let mut position = self
+                .history_view
+                .as_ref()
+                .map_or(live, |view| view.position);
+            if egui::Slider::new(&mut position, 0..=live)
+                .show_value(false)
+                .ui(ui)
+                .changed()
+            {
+                self.show_snapshot(position);
+            }
+            match self
+                .history_view
+                .as_ref()
+                .zip(self.map_history.get(position))
+            {
+                Some((view, snapshot)) => {
+                    let fetched_at = snapshot
+                        .fetched_at
+                        .format(format_description!(
+                            "[year]-[month]-[day] [hour]:[minute]:[second] UTC"
+                        ))
+                        .unwrap();
+                    ui.label(fetched_at);
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        t!("differs_from_live", count = view.changed_cells.len()),
                     );
                 }
+                None => {
+                    ui.label(t!("live"));
+                }
             }
         });
     }
 
+    fn show_snapshot(&mut self, position: usize) {
+        self.history_view = self
+            .map_history
+            .get(position)
+            .and_then(|snapshot| match snapshot.grid() {
+                Ok(grid) => Some(grid),
+                Err(err) => {
+                    log::warn!("Can not restore map snapshot: {err}");
+                    None
+                }
+            })
+            .map(|grid| HistoryView {
+                position,
+                changed_cells: self
+                    .grid
+                    .as_ref()
+                    .map(|live| live.diff(&grid))
+                    .unwrap_or_default(),
+                grid,
+            });
+    }
+
+    fn target_filter_picker(&mut self, ui: &mut Ui) {
+        let emojis = self
+            .emojis
+            .get_or_init(|| new_emoji_map(ui.ctx(), &self.emoji_dir));
+        let emoji_height = ui.text_style_height(&TextStyle::Body);
+        let show_emoji = |ui: &mut Ui, filter: &CellFilter| {
+            if let Some(texture) = emojis.texture(&filter.emoji(), emoji_height) {
+                Image::new(&texture).max_height(emoji_height).ui(ui);
+            }
+        };
+        ui.label(t!("nearest"));
+        if let Some(filter) = &self.target_filter {
+            show_emoji(ui, filter);
+        }
+        egui::ComboBox::from_id_salt("target_filter")
+            .selected_text(
+                self.target_filter
+                    .as_ref()
+                    .map(|filter| filter.label())
+                    .unwrap_or_else(|| t!("by_click").to_string()),
+            )
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(self.target_filter.is_none(), t!("by_click"))
+                    .clicked()
+                    && self.target_filter.is_some()
+                {
+                    self.target_filter = None;
+                    self.need_to_save = true;
+                }
+                for filter in &self.available_filters {
+                    ui.horizontal(|ui| {
+                        show_emoji(ui, filter);
+                        if ui
+                            .selectable_label(
+                                self.target_filter.as_ref() == Some(filter),
+                                filter.label(),
+                            )
+                            .clicked()
+                            && self.target_filter.as_ref() != Some(filter)
+                        {
+                            self.target_filter = Some(filter.clone());
+                            self.need_to_save = true;
+                        }
+                    });
+                }
+            });
+    }
+
     fn load_map(&mut self, ctx: &egui::Context) -> bool {
         if self.grid.is_some() {
+            self.refresh_map(ctx);
             return true;
         }
         let bytes = ctx.try_load_bytes(self.map_url.as_str());
@@ -680,13 +1945,18 @@ impl MarshrutkaApp {
                 return false;
             }
         };
-        match MapGrid::parse(s.as_ref()) {
+        match MapGrid::parse_lenient(s.as_ref()) {
             Ok(grid) => {
-                self.hq_position = CellIndexBuilder::from(self.hq_position)
-                    .clamp(grid.homeland_size() as u8)
-                    .build();
-                self.need_to_save = true;
-                self.grid = Some(grid);
+                self.set_grid(grid);
+                if self
+                    .grid
+                    .as_ref()
+                    .is_some_and(|grid| !grid.errors.is_empty())
+                    || !self.map_issues.is_empty()
+                {
+                    self.show_diagnostics = true;
+                }
+                self.map_loaded_at = ctx.input(|i| i.time);
                 true
             }
             Err(err) => {
@@ -701,32 +1971,386 @@ impl MarshrutkaApp {
         }
     }
 
-    fn update_path(&mut self) -> bool {
-        self.path = self
-            .from
-            .zip(self.to)
-            .and_then(|(from, to)| {
-                FindPath {
-                    homeland: self.homeland,
-                    scroll_of_escape_cost: self.scroll_of_escape_cost,
-                    scroll_of_escape_hq_cost: self.scroll_of_escape_hq_cost,
-                    scroll_of_escape_forum_cost: self.scroll_of_escape_forum_cost,
-                    use_soe: self.use_soe,
-                    hq_position: if self.use_shq {
-                        Some(self.hq_position)
-                    } else {
-                        None
-                    },
-                    use_sfm: self.use_sfm,
-                    use_caravans: self.use_caravans,
-                    route_guru: self.route_guru_skill.into(),
-                    fleetfoot: self.fleetfoot_skill.into(),
-                    sort_by: self.sort_by,
-                    grid: self.grid.as_ref().unwrap(),
+    fn set_grid(&mut self, grid: MapGrid) {
+        self.hq_position = CellIndexBuilder::from(self.hq_position)
+            .clamp(grid.homeland_size() as u8)
+            .build();
+        self.map_issues = validate(&grid);
+        self.available_filters = CellFilter::available(&grid);
+        if self.map_history.push(
+            MapSnapshot::new(OffsetDateTime::now_utc(), &grid),
+            self.map_history_size,
+        ) {
+            self.need_to_save_history = true;
+        }
+        self.history_view = None;
+        self.need_to_save = true;
+        self.grid = Some(grid);
+    }
+
+    /// Alerts about due commands. Repaints are requested only while the timer is running
+    fn poll_trip_timer(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        if let Some((_, flash_until)) = &self.alarm {
+            if now < *flash_until {
+                ctx.request_repaint_after_secs(0.5);
+            } else {
+                self.alarm = None;
+            }
+        }
+        let Some(trip_timer) = &mut self.trip_timer else {
+            return;
+        };
+        let clock = OffsetDateTime::now_utc();
+        if let Some(command) = trip_timer.poll(clock) {
+            if self.alarm_sound {
+                alarm::play_beep();
+            }
+            if self.alarm_notification {
+                alarm::notify(&t!("command_is_due"), command);
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                egui::UserAttentionType::Critical,
+            ));
+            self.alarm = Some((command.to_string(), now + ALARM_FLASH_SECS));
+            ctx.request_repaint();
+        }
+        if !trip_timer.is_finished(clock) {
+            ctx.request_repaint_after_secs(1.0);
+        }
+    }
+
+    fn start_trip_timer(&mut self, path: &TotalCost) {
+        let steps = self
+            .schedule(path)
+            .into_iter()
+            .filter_map(|(command, time)| Some((command.text()?, time)))
+            .collect();
+        self.trip_timer = Some(TripTimer::new(steps, OffsetDateTime::now_utc()));
+    }
+
+    fn start_trip(&mut self, path: &TotalCost) {
+        let Some(destination) = path.commands.last().map(|command| command.to) else {
+            return;
+        };
+        let now = OffsetDateTime::now_utc();
+        self.trip = Some(Trip::new(
+            self.schedule(path),
+            destination,
+            self.arrival(path),
+            now,
+        ));
+        self.trip_cell = None;
+    }
+
+    /// Finds a new route from the cell, where the traveller actually is, to the destination
+    /// of the trip. Arrival time of the original plan is kept.
+    fn replan_trip(&mut self, from: CellIndex) -> bool {
+        let Some(trip) = &self.trip else {
+            return false;
+        };
+        let (destination, arrive_at) = (trip.destination(), trip.arrive_at());
+        let Some(path) = self
+            .find_path()
+            .and_then(|find_path| find_path.eval(from, destination))
+        else {
+            return false;
+        };
+        let schedule = self.schedule_to(&path, arrive_at);
+        if self.trip_timer.is_some() {
+            let steps = schedule
+                .iter()
+                .filter_map(|(command, time)| Some((command.text()?, *time)))
+                .collect();
+            self.trip_timer = Some(TripTimer::new(steps, OffsetDateTime::now_utc()));
+        }
+        if let Some(trip) = &mut self.trip {
+            trip.replan(schedule);
+        }
+        true
+    }
+
+    fn trip(&mut self, ctx: &egui::Context) {
+        let Some(trip) = &self.trip else {
+            return;
+        };
+        let now = OffsetDateTime::now_utc();
+        let clock = self.clock();
+        let reference = clock.to_zone(trip.started_at()).date();
+        let local = |time: OffsetDateTime| clock.format_time(time, reference);
+        let mut open = true;
+        let mut sent = None;
+        let mut replan_from = None;
+        egui::Window::new(t!("trip"))
+            .id(Id::new("trip"))
+            .open(&mut open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(t!("trip_started_at", time = local(trip.started_at())));
+                    ui.label(t!(
+                        "trip_elapsed",
+                        time = format_duration(trip.elapsed(now))
+                    ));
+                    ui.label(t!("trip_arrive_at", time = local(trip.arrive_at())));
+                });
+                let lateness = trip.lateness(now);
+                if lateness.is_positive() {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        t!("trip_late", time = format_duration(lateness)),
+                    );
+                } else if !trip.is_finished() {
+                    ui.label(t!("trip_time_left", time = format_duration(-lateness)));
                 }
-                .eval(from, to)
-            })
-            .map(Rc::new);
+
+                egui::Grid::new("trip_grid").striped(true).show(ui, |ui| {
+                    ui.label("");
+                    ui.label(t!("command"));
+                    ui.label(t!("schedule_at"));
+                    ui.label(t!("sent_at"));
+                    ui.end_row();
+                    let next = trip.next_step().map(|step| step.scheduled_at);
+                    for (i, step) in trip.steps().iter().enumerate() {
+                        let Some(command_str) = step.command.text() else {
+                            continue;
+                        };
+                        let mut is_sent = step.sent_at.is_some();
+                        if ui.checkbox(&mut is_sent, "").changed() {
+                            sent = Some((i, is_sent));
+                        }
+                        let link = egui::Hyperlink::from_label_and_url(
+                            &command_str,
+                            if self.command_via_chat_link {
+                                send_command_to_bot(&command_str)
+                            } else {
+                                send_command(&command_str)
+                            },
+                        )
+                        .open_in_new_tab(true)
+                        .ui(ui);
+                        if !is_sent && next == Some(step.scheduled_at) {
+                            link.highlight();
+                        }
+                        let late = step.sent_at.unwrap_or(now) > step.scheduled_at;
+                        let scheduled_at = local(step.scheduled_at);
+                        if late && !is_sent {
+                            ui.colored_label(ui.visuals().error_fg_color, scheduled_at);
+                        } else {
+                            ui.label(scheduled_at);
+                        }
+                        ui.label(step.sent_at.map(local).unwrap_or_default());
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(t!("off_plan"));
+                    if let Some(grid) = &self.grid {
+                        if let Some(cell) = CellInput::new(
+                            "trip_cell",
+                            &mut self.trip_cell_text,
+                            self.trip_cell.or(trip.current_cell()),
+                            grid,
+                        )
+                        .show(ui)
+                        {
+                            self.trip_cell = Some(cell);
+                        }
+                    }
+                    if ui
+                        .add_enabled(self.trip_cell.is_some(), Button::new(t!("replan")))
+                        .clicked()
+                    {
+                        replan_from = self.trip_cell;
+                    }
+                });
+            });
+        if !open {
+            self.trip = None;
+            return;
+        }
+        if let (Some((step, is_sent)), Some(trip)) = (sent, &mut self.trip) {
+            trip.set_sent(step, is_sent, now);
+        }
+        if let Some(from) = replan_from {
+            if self.replan_trip(from) {
+                self.trip_cell = None;
+            } else {
+                log::warn!("Can not find route from {from}");
+            }
+        }
+        // Elapsed time and lateness are ticking
+        if self.trip.as_ref().is_some_and(|trip| !trip.is_finished()) {
+            ctx.request_repaint_after_secs(1.0);
+        }
+    }
+
+    fn trip_timer_status(&self, ui: &mut Ui) {
+        let Some(trip_timer) = &self.trip_timer else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            if let Some((command, flash_until)) = &self.alarm {
+                // Blinks twice a second
+                let now = ui.input(|i| i.time);
+                let color = if (flash_until - now).fract() < 0.5 {
+                    ui.visuals().error_fg_color
+                } else {
+                    ui.visuals().warn_fg_color
+                };
+                ui.colored_label(color, format!("\u{23f0} {}", t!("command_is_due")));
+                ui.colored_label(color, egui::RichText::new(command).strong());
+            }
+            match trip_timer.next(OffsetDateTime::now_utc()) {
+                Some((command, left)) => {
+                    ui.label(format!("\u{23f1} {}", format_duration(left)));
+                    ui.label(command);
+                }
+                None => {
+                    ui.label(format!("\u{23f1} {}", t!("trip_timer_finished")));
+                }
+            }
+        });
+    }
+
+    fn refresh_map(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        if !self.changed_cells.is_empty() {
+            let highlight_until = self.map_changed_at + MAP_CHANGE_HIGHLIGHT_SECS;
+            if now < highlight_until {
+                ctx.request_repaint_after_secs((highlight_until - now) as f32);
+            } else {
+                self.changed_cells.clear();
+            }
+        }
+        if self.map_refresh_interval == 0 {
+            return;
+        }
+        if !self.map_refreshing {
+            let next_refresh = self.map_loaded_at
+                + (self.map_refresh_interval * Second::per(Minute) as u32) as f64;
+            if now < next_refresh {
+                ctx.request_repaint_after_secs((next_refresh - now) as f32);
+                return;
+            }
+            ctx.forget_image(self.map_url.as_str());
+            self.map_refreshing = true;
+        }
+        match ctx.try_load_bytes(self.map_url.as_str()) {
+            Ok(BytesPoll::Pending { .. }) => {
+                ctx.request_repaint_after_secs(1.0);
+            }
+            Ok(BytesPoll::Ready { bytes, .. }) => {
+                self.map_refreshing = false;
+                self.map_loaded_at = now;
+                match MapGrid::parse_lenient(String::from_utf8_lossy(&bytes).as_ref()) {
+                    Ok(grid) => self.replace_grid(grid, now),
+                    Err(err) => log::warn!("Can not parse refreshed map: {err}"),
+                }
+            }
+            Err(err) => {
+                self.map_refreshing = false;
+                self.map_loaded_at = now;
+                log::warn!("Can not refresh map: {err}");
+            }
+        }
+    }
+
+    fn replace_grid(&mut self, grid: MapGrid, now: f64) {
+        let changed_cells = self
+            .grid
+            .as_ref()
+            .map(|old_grid| old_grid.diff(&grid))
+            .unwrap_or_default();
+        if changed_cells.is_empty() {
+            return;
+        }
+        for cell_index in [&mut self.from, &mut self.to] {
+            if cell_index.is_some_and(|cell_index| !grid.index.contains_key(&cell_index)) {
+                *cell_index = None;
+            }
+        }
+        let old_path = self.path.take();
+        self.set_grid(grid);
+        self.update_path();
+        let route_affected = match (&old_path, &self.path) {
+            (Some(old_path), Some(path)) => {
+                old_path.commands != path.commands
+                    || old_path.commands.iter().any(|command| {
+                        changed_cells.contains(&command.from) || changed_cells.contains(&command.to)
+                    })
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        self.map_update_notice = Some(MapUpdateNotice {
+            changed_cells: changed_cells.len(),
+            route_affected,
+        });
+        self.changed_cells = changed_cells;
+        self.map_changed_at = now;
+    }
+
+    fn route_options(&self) -> RouteOptions {
+        RouteOptions {
+            sort_by: self.sort_by,
+            use_soe: self.use_soe,
+            use_shq: self.use_shq,
+            use_sfm: self.use_sfm,
+            use_caravans: self.use_caravans,
+        }
+    }
+
+    fn find_path(&self) -> Option<FindPath<'_>> {
+        self.find_path_with(self.route_options())
+    }
+
+    fn find_path_with(&self, options: RouteOptions) -> Option<FindPath<'_>> {
+        Some(FindPath {
+            homeland: self.homeland,
+            scroll_of_escape_cost: self.scroll_of_escape_cost,
+            scroll_of_escape_hq_cost: self.scroll_of_escape_hq_cost,
+            scroll_of_escape_forum_cost: self.scroll_of_escape_forum_cost,
+            use_soe: options.use_soe,
+            hq_position: if options.use_shq {
+                Some(self.hq_position)
+            } else {
+                None
+            },
+            use_sfm: options.use_sfm,
+            use_caravans: options.use_caravans,
+            route_guru: self.route_guru_skill.into(),
+            fleetfoot: self.fleetfoot_skill.into(),
+            sort_by: options.sort_by,
+            grid: self.grid.as_ref()?,
+        })
+    }
+
+    fn eval_route(&self, options: RouteOptions) -> Option<TotalCost> {
+        let from = self.from?;
+        let find_path = self.find_path_with(options)?;
+        match (&self.target_filter, self.to) {
+            (Some(filter), _) => find_path.eval_nearest(from, |cell| filter.matches(cell)),
+            (None, Some(to)) => find_path.eval(from, to),
+            (None, None) => None,
+        }
+    }
+
+    fn update_path(&mut self) -> bool {
+        self.hover_cost = None;
+        self.path = self.eval_route(self.route_options()).map(Rc::new);
+        self.pinned_paths = self
+            .pinned_routes
+            .iter()
+            .map(|route| self.eval_route(route.options).map(Rc::new))
+            .collect();
+        if self.target_filter.is_some() {
+            if let Some(path) = &self.path {
+                self.to = path.commands.last().map(|command| command.to);
+            }
+        }
         self.path.is_some()
     }
 
@@ -743,6 +2367,13 @@ impl MarshrutkaApp {
         } else if self.path.is_none() && self.update_path() {
             ctx.request_repaint();
         }
+
+        if self.need_to_save_history {
+            self.need_to_save_history = false;
+            if let Some(storage) = frame.storage_mut() {
+                eframe::set_value(storage, MAP_HISTORY_KEY, &self.map_history);
+            }
+        }
     }
 
     fn save_app(&mut self, storage: &mut dyn eframe::Storage) {
@@ -753,6 +2384,9 @@ impl MarshrutkaApp {
 impl eframe::App for MarshrutkaApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Alarms go off even while the map is loading
+        self.poll_trip_timer(ctx);
+
         // Prepare data
         if !self.load_map(ctx) {
             return;
@@ -765,6 +2399,10 @@ impl eframe::App for MarshrutkaApp {
         // Windows
         self.settings(ctx);
         self.about(ctx);
+        self.diagnostics(ctx);
+        self.bookmarks(ctx);
+        self.notes(ctx);
+        self.trip(ctx);
 
         // Central panel. Should be added after all other panels
         self.central_panel(ctx);
@@ -785,9 +2423,15 @@ impl Default for MarshrutkaApp {
             emojis: Default::default(),
             show_settings: true,
             show_about: true,
+            show_diagnostics: Default::default(),
+            show_bookmarks: Default::default(),
+            show_notes: Default::default(),
             grid: Default::default(),
+            map_issues: Default::default(),
             from: Default::default(),
             to: Default::default(),
+            target_filter: Default::default(),
+            available_filters: Default::default(),
             homeland: Default::default(),
             need_to_save: Default::default(),
             sort_by: (CostComparator::Legs, CostComparator::Money),
@@ -799,14 +2443,115 @@ impl Default for MarshrutkaApp {
             use_shq: false,
             use_sfm: false,
             use_caravans: true,
-            arrive_at: Time::MIDNIGHT,
+            schedule_mode: Default::default(),
+            schedule_time: Time::MIDNIGHT,
+            schedule_date: OffsetDateTime::now_utc().date(),
+            clock_zone: Default::default(),
+            server_utc_offset: UtcOffset::from_hms(3, 0, 0).unwrap(),
+            calendar_reminder: 1,
             pause_between_steps: Default::default(),
             path: Default::default(),
             map_url: DEFAULT_MAP_URL.to_string(),
+            emoji_dir: Default::default(),
+            map_refresh_interval: Default::default(),
+            map_loaded_at: Default::default(),
+            map_refreshing: Default::default(),
+            changed_cells: Default::default(),
+            map_changed_at: Default::default(),
+            map_update_notice: Default::default(),
+            map_history_size: 10,
+            map_view: Default::default(),
+            pinned_routes: Default::default(),
+            theme: Default::default(),
+            pinned_paths: Default::default(),
+            map_history: Default::default(),
+            need_to_save_history: Default::default(),
+            history_view: Default::default(),
+            hover_cost: Default::default(),
+            from_text: Default::default(),
+            to_text: Default::default(),
+            bookmarks: Default::default(),
+            new_bookmark: Default::default(),
+            bookmarks_status: Default::default(),
+            bookmarks_import_text: Default::default(),
+            export_status: Default::default(),
+            notes: Default::default(),
+            highlight_tags: Default::default(),
+            notes_query: Default::default(),
+            note_edit: Default::default(),
             command_via_chat_link: Default::default(),
+            trip_timer: Default::default(),
+            trip: Default::default(),
+            trip_cell: Default::default(),
+            trip_cell_text: Default::default(),
+            alarm: Default::default(),
+            alarm_sound: true,
+            alarm_notification: Default::default(),
             route_guru_skill: Default::default(),
             fleetfoot_skill: Default::default(),
             translation: Default::default(),
         }
     }
 }
+
+fn new_emoji_map(ctx: &egui::Context, emoji_dir: &str) -> EmojiMap {
+    let emoji_dir = emoji_dir.trim();
+    EmojiMap::new(ctx).with_dir((!emoji_dir.is_empty()).then(|| PathBuf::from(emoji_dir)))
+}
+
+/// Result of an import or export, errors are highlighted
+fn status_label(ui: &mut Ui, status: &Result<String, String>) {
+    match status {
+        Ok(status) => {
+            ui.label(status);
+        }
+        Err(err) => {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+    }
+}
+
+fn scrolls_used(path: &TotalCost) -> String {
+    let scrolls: Vec<_> = [
+        MoveKind::ScrollOfEscape,
+        MoveKind::ScrollOfEscapeHQ,
+        MoveKind::ScrollOfEscapeForum,
+    ]
+    .into_iter()
+    .filter_map(|kind| {
+        let count = path
+            .commands
+            .iter()
+            .filter(|command| command.aggregated_cost.kind() == Some(kind))
+            .count();
+        (count > 0).then(|| format!("{} ×{count}", t!(kind.name())))
+    })
+    .collect();
+    if scrolls.is_empty() {
+        "—".to_string()
+    } else {
+        scrolls.join(", ")
+    }
+}
+
+impl Display for RouteOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} → {}",
+            t!(self.sort_by.0.as_str()),
+            t!(self.sort_by.1.as_str())
+        )?;
+        for (enabled, name) in [
+            (self.use_soe, "SoE".into()),
+            (self.use_shq, "SHQ".into()),
+            (self.use_sfm, "SFm".into()),
+            (self.use_caravans, t!("caravans")),
+        ] {
+            if enabled {
+                write!(f, ", {name}")?;
+            }
+        }
+        Ok(())
+    }
+}
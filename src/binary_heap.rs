@@ -128,24 +128,17 @@ pub struct BinaryHeap<T, C> {
     cmp: C,
 }
 
+#[cfg(test)]
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub struct MaxComparator;
 
+#[cfg(test)]
 impl<T: Ord> Compare<T> for MaxComparator {
     fn compare(&self, a: &T, b: &T) -> Ordering {
         a.cmp(b)
     }
 }
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
-pub struct MinComparator;
-
-impl<T: Ord> Compare<T> for MinComparator {
-    fn compare(&self, a: &T, b: &T) -> Ordering {
-        b.cmp(a)
-    }
-}
-
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub struct FnComparator<F>(pub F);
 
@@ -0,0 +1,183 @@
+use crate::index::CellIndex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Named favourite location, like "my shop" or "guild HQ"
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub cell: CellIndex,
+}
+
+/// Bookmarks in the order they were added.
+/// They are shared as text file with `name = cell` lines, e.g. `guild HQ = B 3#4`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bookmarks(pub Vec<Bookmark>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BookmarkError {
+    /// Line has no `=` or an empty name
+    Syntax {
+        line: usize,
+    },
+    UnknownCell {
+        line: usize,
+        text: String,
+    },
+}
+
+impl Bookmarks {
+    pub fn parse(s: &str) -> Result<Self, BookmarkError> {
+        s.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(line, text)| {
+                // Name may contain `=`, cell notation doesn't
+                let (name, cell) = text
+                    .rsplit_once('=')
+                    .map(|(name, cell)| (name.trim(), cell.trim()))
+                    .filter(|(name, _)| !name.is_empty())
+                    .ok_or(BookmarkError::Syntax { line })?;
+                let cell =
+                    CellIndex::parse_any(cell).ok_or_else(|| BookmarkError::UnknownCell {
+                        line,
+                        text: cell.to_string(),
+                    })?;
+                Ok(Bookmark {
+                    name: name.to_string(),
+                    cell,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Bookmarks with empty names are written with the cell as the name, so they can be parsed
+    pub fn to_text(&self) -> String {
+        self.0
+            .iter()
+            .map(|bookmark| {
+                let name = match bookmark.name.trim() {
+                    "" => bookmark.cell.to_string(),
+                    name => name.to_string(),
+                };
+                format!("{name} = {}\n", bookmark.cell)
+            })
+            .collect()
+    }
+
+    /// Adds bookmark or moves existing one with the same name
+    pub fn insert(&mut self, bookmark: Bookmark) {
+        match self.0.iter_mut().find(|b| b.name == bookmark.name) {
+            Some(existing) => existing.cell = bookmark.cell,
+            None => self.0.push(bookmark),
+        }
+    }
+
+    /// Merges imported bookmarks, imported cells win on the same names
+    pub fn merge(&mut self, other: Bookmarks) {
+        for bookmark in other.0 {
+            self.insert(bookmark);
+        }
+    }
+
+    pub fn at(&self, cell: CellIndex) -> impl Iterator<Item = &Bookmark> {
+        self.0.iter().filter(move |bookmark| bookmark.cell == cell)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Display for BookmarkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookmarkError::Syntax { line } => {
+                write!(f, "Line {line}: expected `name = cell`")
+            }
+            BookmarkError::UnknownCell { line, text } => {
+                write!(f, "Line {line}: unknown cell {text:?}")
+            }
+        }
+    }
+}
+
+impl Error for BookmarkError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let bookmarks =
+            Bookmarks::parse("my shop = B 3#4\n\n guild = HQ = /go_direct_yb_2 \nmine=0#0")
+                .unwrap();
+        assert_eq!(
+            bookmarks.0,
+            [
+                Bookmark {
+                    name: "my shop".to_string(),
+                    cell: CellIndex::parse_any("B3#4").unwrap(),
+                },
+                Bookmark {
+                    name: "guild = HQ".to_string(),
+                    cell: CellIndex::parse_any("YB2").unwrap(),
+                },
+                Bookmark {
+                    name: "mine".to_string(),
+                    cell: CellIndex::Center,
+                },
+            ]
+        );
+        assert_eq!(Bookmarks::parse(&bookmarks.to_text()), Ok(bookmarks));
+    }
+
+    #[test]
+    fn empty_name_round_trip() {
+        let cell = CellIndex::parse_any("B1#1").unwrap();
+        let bookmarks = Bookmarks(vec![Bookmark {
+            name: " ".to_string(),
+            cell,
+        }]);
+        assert_eq!(
+            Bookmarks::parse(&bookmarks.to_text()).unwrap().0,
+            [Bookmark {
+                name: cell.to_string(),
+                cell,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Bookmarks::parse("a = 0#0\nno cell"),
+            Err(BookmarkError::Syntax { line: 2 })
+        );
+        assert_eq!(
+            Bookmarks::parse(" = 0#0"),
+            Err(BookmarkError::Syntax { line: 1 })
+        );
+        assert_eq!(
+            Bookmarks::parse("a = Q 1#1"),
+            Err(BookmarkError::UnknownCell {
+                line: 1,
+                text: "Q 1#1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn merge_replaces_same_names() {
+        let mut bookmarks = Bookmarks::parse("shop = 0#0\nmine = B 1#1").unwrap();
+        bookmarks.merge(Bookmarks::parse("mine = G 2#2\nforge = R 1#1").unwrap());
+        assert_eq!(
+            bookmarks.to_text(),
+            "shop = 0#0\nmine = G 2#2\nforge = R 1#1\n"
+        );
+    }
+}
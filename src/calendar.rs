@@ -0,0 +1,162 @@
+use time::macros::format_description;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+/// Step of the schedule as an iCalendar event, e.g. `/go_direct_b_3_4` from 12:00 to 12:05
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub description: String,
+    pub start: OffsetDateTime,
+    pub duration: Duration,
+}
+
+/// iCalendar (RFC 5545) file with a reminder before each event.
+/// Times are written in UTC, so the file doesn't need time zone definitions.
+#[derive(Clone, Debug, Default)]
+pub struct Calendar {
+    events: Vec<CalendarEvent>,
+    reminder: Duration,
+}
+
+/// Lines longer than 75 octets are folded
+const MAX_LINE_LEN: usize = 75;
+
+impl Calendar {
+    pub fn new(events: Vec<CalendarEvent>) -> Self {
+        Self {
+            events,
+            ..Self::default()
+        }
+    }
+
+    /// How long before each event the alarm goes off
+    pub fn reminder(self, reminder: Duration) -> Self {
+        Self { reminder, ..self }
+    }
+
+    /// Calendar text with CRLF line endings, `stamp` is the creation time
+    pub fn to_ics(&self, stamp: OffsetDateTime) -> String {
+        let mut ics = String::new();
+        let mut line = |s: &str| push_folded(&mut ics, s);
+        line("BEGIN:VCALENDAR");
+        line("VERSION:2.0");
+        line(concat!(
+            "PRODID:-//marshrutka//",
+            env!("CARGO_PKG_VERSION"),
+            "//EN"
+        ));
+        line("CALSCALE:GREGORIAN");
+        for (i, event) in self.events.iter().enumerate() {
+            line("BEGIN:VEVENT");
+            line(&format!(
+                "UID:{}-{i}@marshrutka",
+                event.start.unix_timestamp()
+            ));
+            line(&format!("DTSTAMP:{}", format_utc(stamp)));
+            line(&format!("DTSTART:{}", format_utc(event.start)));
+            line(&format!(
+                "DTEND:{}",
+                format_utc(event.start + event.duration)
+            ));
+            line(&format!("SUMMARY:{}", escape(&event.summary)));
+            if !event.description.is_empty() {
+                line(&format!("DESCRIPTION:{}", escape(&event.description)));
+            }
+            line("BEGIN:VALARM");
+            line("ACTION:DISPLAY");
+            line(&format!("DESCRIPTION:{}", escape(&event.summary)));
+            line(&format!(
+                "TRIGGER:-PT{}S",
+                self.reminder.whole_seconds().max(0)
+            ));
+            line("END:VALARM");
+            line("END:VEVENT");
+        }
+        line("END:VCALENDAR");
+        ics
+    }
+}
+
+/// Date-time in the `20250102T030405Z` form
+fn format_utc(date_time: OffsetDateTime) -> String {
+    date_time
+        .to_offset(UtcOffset::UTC)
+        .format(format_description!(
+            "[year][month][day]T[hour][minute][second]Z"
+        ))
+        .unwrap()
+}
+
+/// Escapes TEXT value
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Appends content line, continuation lines start with a space
+fn push_folded(ics: &mut String, line: &str) {
+    let mut len = 0;
+    for ch in line.chars() {
+        if len + ch.len_utf8() > MAX_LINE_LEN {
+            ics.push_str("\r\n ");
+            len = 1;
+        }
+        ics.push(ch);
+        len += ch.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn events_with_alarms() {
+        let calendar = Calendar::new(vec![CalendarEvent {
+            summary: "/go_direct_b_3_4".to_string(),
+            description: "0#0 → B 3#4".to_string(),
+            start: datetime!(2025-01-01 00:10:00 +03:00),
+            duration: Duration::minutes(5),
+        }])
+        .reminder(Duration::minutes(1));
+        let ics = calendar.to_ics(datetime!(2024-12-31 12:00:00 UTC));
+        let lines: Vec<_> = ics.split_terminator("\r\n").collect();
+        assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+        assert_eq!(lines.last(), Some(&"END:VCALENDAR"));
+        for expected in [
+            "DTSTAMP:20241231T120000Z",
+            "DTSTART:20241231T211000Z",
+            "DTEND:20241231T211500Z",
+            "SUMMARY:/go_direct_b_3_4",
+            "DESCRIPTION:0#0 → B 3#4",
+            "TRIGGER:-PT60S",
+        ] {
+            assert!(lines.contains(&expected), "{expected} is missing in {ics}");
+        }
+    }
+
+    #[test]
+    fn escape_and_fold() {
+        assert_eq!(escape("a,b;c\\d\r\ne"), "a\\,b\\;c\\\\d\\ne");
+        let mut ics = String::new();
+        push_folded(&mut ics, &"я".repeat(40));
+        let lines: Vec<_> = ics.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LEN));
+        assert_eq!(lines[0].len(), 74);
+        assert!(lines[1].starts_with(' '));
+    }
+}
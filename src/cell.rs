@@ -1,20 +1,24 @@
 use crate::consts::{
-    BLEACH_ALPHA, CELL_MARGIN, CELL_ROUNDING, CELL_SIZE, FONT_CENTER, FONT_CORNER,
+    BLEACH_ALPHA, CELL_MARGIN, CELL_ROUNDING, EMOJI_CORNER_SIZE, FONT_CENTER, FONT_CENTER_SIZE,
+    FONT_CORNER, HIGHLIGHT_CELL_STROKE, INVALID_CELL_STROKE, NOTE_BADGE_COLOR, NOTE_BADGE_SIZE,
 };
+use crate::content::CellContents;
 use crate::emoji::{EmojiCode, EmojiMap};
-use crate::grid::PoI;
 use crate::homeland::Homeland;
 use crate::index::CellIndex;
-use arrayvec::ArrayVec;
 use egui::{
-    Align2, Color32, Margin, Painter, Pos2, Rect, Sense, TextStyle, TextureHandle, Ui, Vec2,
+    Align2, Color32, FontId, Painter, Pos2, Rect, Shape, Stroke, StrokeKind, TextStyle,
+    TextureHandle, Ui, Vec2,
 };
 use enum_map::EnumMap;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::OnceCell;
 use std::fmt::{Display, Formatter};
+use std::iter;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum CellElement {
     Text(String),
     Emoji(EmojiCode),
@@ -29,16 +33,27 @@ pub struct Cell {
     pub bottom_right: Option<CellElement>,
     pub center: Option<CellElement>,
     pub index: CellIndex,
-    pub poi: Option<PoI>,
+    pub contents: CellContents,
     pub x: i8,
     pub y: i8,
+    /// Cell was malformed in map source and is restored by its position
+    pub invalid: bool,
     pub nearest_campfire: OnceCell<EnumMap<Homeland, Option<CellIndex>>>,
 }
 
+/// Marks, which are painted over the cell
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CellMarks {
+    pub highlight: Option<Color32>,
+    /// Cell has user's note
+    pub note: bool,
+}
+
 struct DrawAttrs {
     align: Align2,
     large: bool,
     rect: Rect,
+    zoom: f32,
     bleach: bool,
 }
 
@@ -49,23 +64,27 @@ impl TryFrom<&str> for CellElement {
         if s.is_empty() {
             return Err(());
         }
-        let emoji: ArrayVec<_, 3> = s.chars().take(3).collect();
-        Ok(EmojiCode::try_from(emoji.as_ref())
+        Ok(EmojiCode::parse(s)
             .map(Self::Emoji)
-            .unwrap_or_else(|_| Self::Text(s.to_string())))
+            .unwrap_or_else(|| Self::Text(s.to_string())))
     }
 }
 
 impl Cell {
-    pub fn ui_content(
+    /// Paints cell into `rect`, which is already scaled by `zoom`
+    pub fn paint(
         &self,
-        ui: &mut Ui,
+        ui: &Ui,
+        painter: &Painter,
         emoji_map: &EmojiMap,
-    ) -> (Pos2, Option<CellIndex>, Option<CellIndex>) {
-        let (response, painter) = ui.allocate_painter(Vec2::splat(CELL_SIZE), Sense::click());
-        let rect = response.rect - Margin::same(CELL_MARGIN);
+        cell_rect: Rect,
+        zoom: f32,
+        marks: CellMarks,
+    ) {
+        let rect = cell_rect.shrink(CELL_MARGIN * zoom);
+        let rounding = CELL_ROUNDING * zoom;
         if let Some(bg_color) = self.bg_color {
-            painter.rect_filled(response.rect, CELL_ROUNDING, bg_color);
+            painter.rect_filled(cell_rect, rounding, bg_color);
         }
 
         for (cell_element, align, large, bleach) in [
@@ -77,31 +96,59 @@ impl Cell {
         ] {
             self.draw_element(
                 ui,
-                &painter,
+                painter,
                 emoji_map,
                 cell_element,
                 DrawAttrs {
                     align,
                     large,
                     rect,
+                    zoom,
                     bleach,
                 },
             );
         }
 
-        (
-            rect.center(),
-            if response.clicked() {
-                Some(self.index)
-            } else {
-                None
-            },
-            if response.secondary_clicked() {
-                Some(self.index)
-            } else {
-                None
-            },
-        )
+        if marks.note {
+            // Folded corner, like a note in a spreadsheet
+            let size = NOTE_BADGE_SIZE * zoom;
+            let corner = cell_rect.left_top();
+            painter.add(Shape::convex_polygon(
+                vec![
+                    corner + Vec2::new(rounding, 0.0),
+                    corner + Vec2::new(size, 0.0),
+                    corner + Vec2::new(0.0, size),
+                    corner + Vec2::new(0.0, rounding),
+                ],
+                NOTE_BADGE_COLOR,
+                Stroke::NONE,
+            ));
+        }
+
+        if let Some(highlight) = marks.highlight {
+            painter.rect_stroke(
+                cell_rect,
+                rounding,
+                Stroke::new(HIGHLIGHT_CELL_STROKE, highlight),
+                StrokeKind::Inside,
+            );
+        }
+
+        if self.invalid {
+            painter.rect_stroke(
+                cell_rect,
+                rounding,
+                Stroke::new(INVALID_CELL_STROKE, ui.visuals().warn_fg_color),
+                StrokeKind::Inside,
+            );
+            painter.text(
+                Align2::CENTER_TOP.pos_in_rect(&rect),
+                Align2::CENTER_TOP,
+                "⚠",
+                scaled_font(ui, FONT_CORNER, zoom),
+                ui.visuals().warn_fg_color,
+            );
+        }
     }
 
     fn draw_element(
@@ -114,14 +161,22 @@ impl Cell {
     ) {
         if let Some(cell_element) = &cell_element {
             match cell_element {
-                CellElement::Emoji(emoji_code) => match emoji_map.get_texture(emoji_code) {
-                    None => {
-                        self.draw_text(ui, painter, emoji_code, attrs);
+                CellElement::Emoji(emoji_code) => {
+                    let size = attrs.zoom
+                        * if attrs.large {
+                            FONT_CENTER_SIZE
+                        } else {
+                            EMOJI_CORNER_SIZE
+                        };
+                    match emoji_map.texture(emoji_code, size) {
+                        None => {
+                            self.draw_text(ui, painter, emoji_code, attrs);
+                        }
+                        Some(texture) => {
+                            self.draw_emoji_image(painter, (&texture, Vec2::splat(size)), attrs);
+                        }
                     }
-                    Some(texture) => {
-                        self.draw_emoji_image(painter, texture.get(attrs.large), attrs);
-                    }
-                },
+                }
                 CellElement::Text(text) => {
                     self.draw_text(ui, painter, text, attrs);
                 }
@@ -139,7 +194,7 @@ impl Cell {
             attrs.align.pos_in_rect(&attrs.rect),
             attrs.align,
             text,
-            ui.style().text_styles[&TextStyle::Name(font_size.into())].clone(),
+            scaled_font(ui, font_size, attrs.zoom),
             Color32::from_rgba_unmultiplied(
                 0x2c,
                 0x3e,
@@ -169,6 +224,26 @@ impl Cell {
         );
     }
 
+    /// Textual description for screen readers
+    pub fn description(&self) -> String {
+        iter::once(self.index.to_string())
+            .chain(self.contents.iter().map(|content| content.to_string()))
+            .chain(self.invalid.then(|| t!("invalid_cell").to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether cells look the same on the map
+    pub fn same_as(&self, other: &Cell) -> bool {
+        self.bg_color == other.bg_color
+            && self.top_left == other.top_left
+            && self.top_right == other.top_right
+            && self.bottom_left == other.bottom_left
+            && self.bottom_right == other.bottom_right
+            && self.center == other.center
+            && (self.x, self.y) == (other.x, other.y)
+    }
+
     pub fn distance(&self, other: &Cell) -> usize {
         let Cell {
             x: from_x,
@@ -185,19 +260,14 @@ impl Cell {
     }
 }
 
-fn manhattan_distance((from_x, from_y): (isize, isize), (to_x, to_y): (isize, isize)) -> usize {
-    from_x.abs_diff(to_x) + from_y.abs_diff(to_y)
+fn scaled_font(ui: &Ui, name: &str, zoom: f32) -> FontId {
+    let mut font_id = ui.style().text_styles[&TextStyle::Name(name.into())].clone();
+    font_id.size *= zoom;
+    font_id
 }
 
-pub fn cell_parts(center: &Option<CellElement>) -> Option<PoI> {
-    Some(match center {
-        Some(CellElement::Emoji(EmojiCode('\u{1f525}', None))) => PoI::Campfire,
-        Some(CellElement::Emoji(EmojiCode('\u{26f2}', None)))
-        | Some(CellElement::Emoji(EmojiCode('\u{26f2}', Some('\u{fe0f}')))) => PoI::Fountain,
-        Some(CellElement::Emoji(EmojiCode('\u{1f3db}', None)))
-        | Some(CellElement::Emoji(EmojiCode('\u{1F3DB}', Some('\u{fe0f}')))) => PoI::Forum,
-        _ => return None,
-    })
+fn manhattan_distance((from_x, from_y): (isize, isize), (to_x, to_y): (isize, isize)) -> usize {
+    from_x.abs_diff(to_x) + from_y.abs_diff(to_y)
 }
 
 impl From<String> for CellElement {
@@ -0,0 +1,115 @@
+use crate::consts::{CELL_INPUT_SUGGESTIONS, CELL_INPUT_WIDTH};
+use crate::grid::MapGrid;
+use crate::index::{CellIndex, CellIndexCommandSuffix};
+use egui::{PopupCloseBehavior, TextEdit, Ui, Widget};
+use rust_i18n::t;
+
+/// Text field for a cell in any notation, see [`CellIndex::parse_any`]
+pub struct CellInput<'a> {
+    id_salt: &'a str,
+    text: &'a mut String,
+    current: Option<CellIndex>,
+    grid: &'a MapGrid,
+}
+
+impl<'a> CellInput<'a> {
+    pub fn new(
+        id_salt: &'a str,
+        text: &'a mut String,
+        current: Option<CellIndex>,
+        grid: &'a MapGrid,
+    ) -> Self {
+        Self {
+            id_salt,
+            text,
+            current,
+            grid,
+        }
+    }
+
+    /// Returns cell, which is entered or picked from suggestions
+    pub fn show(self, ui: &mut Ui) -> Option<CellIndex> {
+        let id = ui.make_persistent_id(self.id_salt);
+        let popup_id = id.with("suggestions");
+        let has_focus = ui.memory(|memory| memory.has_focus(id));
+        if !has_focus && !ui.memory(|memory| memory.is_popup_open(popup_id)) {
+            *self.text = self
+                .current
+                .map(|cell| cell.to_string())
+                .unwrap_or_default();
+        }
+        let parsed = CellIndex::parse_any(self.text);
+        let valid = parsed.is_some_and(|cell| self.grid.index.contains_key(&cell));
+        let invalid = !self.text.is_empty() && !valid;
+        let mut text_edit = TextEdit::singleline(self.text)
+            .id(id)
+            .desired_width(CELL_INPUT_WIDTH)
+            .hint_text("B 3#4");
+        if invalid {
+            text_edit = text_edit.text_color(ui.visuals().error_fg_color);
+        }
+        let mut response = text_edit.ui(ui);
+        if invalid {
+            response = response.on_hover_text(if parsed.is_some() {
+                t!("cell_not_on_map")
+            } else {
+                t!("unknown_cell_notation")
+            });
+        }
+
+        let mut picked = None;
+        if response.lost_focus() && valid {
+            picked = parsed;
+        }
+        let suggestions = self.suggestions();
+        // Popup is closed by a click, so clicked suggestion is still shown on this frame
+        let focus_left_by_keyboard = response.lost_focus() && !ui.input(|i| i.pointer.any_click());
+        if response.has_focus() && !suggestions.is_empty() {
+            ui.memory_mut(|memory| memory.open_popup(popup_id));
+        } else if (response.has_focus() && suggestions.is_empty()) || focus_left_by_keyboard {
+            ui.memory_mut(|memory| {
+                if memory.is_popup_open(popup_id) {
+                    memory.close_popup();
+                }
+            });
+        }
+        egui::popup_below_widget(
+            ui,
+            popup_id,
+            &response,
+            PopupCloseBehavior::CloseOnClick,
+            |ui| {
+                for cell in suggestions {
+                    if ui.selectable_label(false, cell.to_string()).clicked() {
+                        picked = Some(cell);
+                    }
+                }
+            },
+        );
+        if let Some(cell) = picked {
+            *self.text = cell.to_string();
+        }
+        picked
+    }
+
+    /// Cells of the grid, which start with the entered text in any notation
+    fn suggestions(&self) -> Vec<CellIndex> {
+        let text = self.text.trim().to_lowercase();
+        if text.is_empty() || CellIndex::parse_any(&text).is_some() {
+            return vec![];
+        }
+        let mut suggestions: Vec<_> = self
+            .grid
+            .index
+            .keys()
+            .copied()
+            .filter(|cell| {
+                cell.to_string().to_lowercase().starts_with(&text)
+                    || CellIndexCommandSuffix(*cell).to_string().starts_with(&text)
+            })
+            .collect();
+        suggestions.sort();
+        suggestions.truncate(CELL_INPUT_SUGGESTIONS);
+        suggestions
+    }
+}
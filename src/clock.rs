@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoStaticStr};
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+/// Time zone, which schedule times are entered and shown in
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize, EnumIter, IntoStaticStr,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum ClockZone {
+    /// Time zone of the system or the browser
+    #[default]
+    Local,
+    Utc,
+    /// Time zone of the game server
+    Server,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Clock {
+    pub zone: ClockZone,
+    pub server_offset: UtcOffset,
+}
+
+impl ClockZone {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl Clock {
+    /// Offset of the zone at the moment, local offset depends on daylight saving time
+    pub fn offset_at(&self, at: OffsetDateTime) -> UtcOffset {
+        match self.zone {
+            ClockZone::Local => local_offset(at),
+            ClockZone::Utc => UtcOffset::UTC,
+            ClockZone::Server => self.server_offset,
+        }
+    }
+
+    pub fn to_zone(self, at: OffsetDateTime) -> OffsetDateTime {
+        at.to_offset(self.offset_at(at))
+    }
+
+    /// Moment of the date and time on the clock
+    pub fn assume(&self, date_time: PrimitiveDateTime) -> OffsetDateTime {
+        // Offset is checked twice, as the first guess may be on the other side of a DST change
+        let offset = self.offset_at(date_time.assume_utc());
+        let offset = self.offset_at(date_time.assume_offset(offset));
+        date_time.assume_offset(offset)
+    }
+
+    /// Time of day, like `12:30:00`. Days before or after the `reference` date are marked,
+    /// like `23:50:00 -1d`
+    pub fn format_time(&self, at: OffsetDateTime, reference: Date) -> String {
+        let at = self.to_zone(at);
+        let time = at
+            .format(format_description!("[hour]:[minute]:[second]"))
+            .unwrap();
+        match (at.date() - reference).whole_days() {
+            0 => time,
+            days => format!("{time} {days:+}d"),
+        }
+    }
+}
+
+/// Duration in `[-][Nd ]hh:mm:ss` form, like `1d 02:03:04`
+pub fn format_duration(duration: Duration) -> String {
+    let sign = if duration.is_negative() { "-" } else { "" };
+    let seconds = duration.whole_seconds().unsigned_abs();
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match seconds / 86400 {
+        0 => format!("{sign}{time}"),
+        days => format!("{sign}{days}d {time}"),
+    }
+}
+
+/// Falls back to UTC, if the system time zone is unknown
+#[cfg(not(target_arch = "wasm32"))]
+fn local_offset(at: OffsetDateTime) -> UtcOffset {
+    use jiff::tz::TimeZone;
+    use std::sync::OnceLock;
+
+    static SYSTEM_TIME_ZONE: OnceLock<TimeZone> = OnceLock::new();
+    let time_zone = SYSTEM_TIME_ZONE.get_or_init(|| {
+        TimeZone::try_system().unwrap_or_else(|err| {
+            log::warn!("Can not get system time zone, UTC is used instead: {err}");
+            TimeZone::UTC
+        })
+    });
+    jiff::Timestamp::from_second(at.unix_timestamp())
+        .ok()
+        .map(|timestamp| time_zone.to_offset(timestamp))
+        .and_then(|offset| UtcOffset::from_whole_seconds(offset.seconds()).ok())
+        .unwrap_or(UtcOffset::UTC)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_offset(at: OffsetDateTime) -> UtcOffset {
+    let date = js_sys::Date::new(&(at.unix_timestamp() as f64 * 1000.0).into());
+    // Browser gives minutes from local time to UTC
+    let minutes = -date.get_timezone_offset() as i32;
+    UtcOffset::from_whole_seconds(minutes * 60).unwrap_or(UtcOffset::UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, datetime, offset};
+
+    #[test]
+    fn zones() {
+        let at = datetime!(2025-01-01 00:10:00 UTC);
+        let server = Clock {
+            zone: ClockZone::Server,
+            server_offset: offset!(+3),
+        };
+        assert_eq!(server.to_zone(at), datetime!(2025-01-01 03:10:00 +3));
+        assert_eq!(server.assume(datetime!(2025-01-01 03:10:00)), at);
+        let utc = Clock {
+            zone: ClockZone::Utc,
+            ..server
+        };
+        assert_eq!(utc.assume(datetime!(2025-01-01 00:10:00)), at);
+        let local = Clock {
+            zone: ClockZone::Local,
+            ..server
+        };
+        let local_time = local.to_zone(at);
+        assert_eq!(
+            local.assume(PrimitiveDateTime::new(local_time.date(), local_time.time())),
+            at
+        );
+    }
+
+    #[test]
+    fn date_change() {
+        let utc = Clock {
+            zone: ClockZone::Utc,
+            server_offset: UtcOffset::UTC,
+        };
+        let reference = date!(2025 - 01 - 01);
+        assert_eq!(
+            utc.format_time(datetime!(2025-01-01 00:10:00 UTC), reference),
+            "00:10:00"
+        );
+        assert_eq!(
+            utc.format_time(datetime!(2025-01-01 00:10:00 +3), reference),
+            "21:10:00 -1d"
+        );
+        assert_eq!(
+            utc.format_time(datetime!(2025-01-03 01:00:00 UTC), reference),
+            "01:00:00 +2d"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::seconds(61)), "00:01:01");
+        assert_eq!(
+            format_duration(Duration::hours(26) + Duration::seconds(4)),
+            "1d 02:00:04"
+        );
+        assert_eq!(format_duration(-Duration::minutes(90)), "-01:30:00");
+    }
+}
@@ -1,24 +1,61 @@
+use egui::{Color32, Key, KeyboardShortcut, Modifiers};
 use time::Duration;
 
 pub const DEFAULT_MAP_URL: &str = "https://maratik.fyi/api/chatwars/webview/map";
 
+pub const EXPORT_PNG_FILE: &str = "marshrutka-map.png";
+pub const EXPORT_SVG_FILE: &str = "marshrutka-map.svg";
+pub const BOOKMARKS_FILE: &str = "marshrutka-bookmarks.txt";
+pub const SCHEDULE_ICS_FILE: &str = "marshrutka-schedule.ics";
+
 pub const FONT_CENTER: &str = "center";
 pub const FONT_CENTER_SIZE: f32 = 32.0;
 
 pub const FONT_CORNER: &str = "corner";
 pub const FONT_CORNER_SIZE: f32 = 12.0;
 pub const EMOJI_CORNER_SIZE: f32 = 16.0;
+/// Emoji texture width in pixels is a multiple of it
+pub const EMOJI_TEXTURE_STEP: u32 = 8;
 
 pub const GRID_SPACING: f32 = 2.0;
 
 pub const CELL_SIZE: f32 = 62.0;
-pub const CELL_MARGIN: i8 = 4;
+pub const CELL_MARGIN: f32 = 4.0;
 pub const CELL_ROUNDING: f32 = 5.0;
+pub const MIN_MAP_HEIGHT: f32 = 200.0;
+pub const MIN_MAP_ZOOM: f32 = 0.25;
+pub const MAX_MAP_ZOOM: f32 = 4.0;
+/// Zoom factor exponent per point of mouse wheel scroll
+pub const MAP_ZOOM_SPEED: f32 = 1.0 / 200.0;
+pub const INVALID_CELL_STROKE: f32 = 2.0;
+pub const HIGHLIGHT_CELL_STROKE: f32 = 3.0;
+pub const CURSOR_CELL_STROKE: f32 = 3.0;
+pub const BOOKMARK_MARKER_RADIUS: f32 = 7.0;
+pub const NOTE_BADGE_SIZE: f32 = 12.0;
+pub const NOTE_BADGE_COLOR: Color32 = Color32::from_rgb(0x8e, 0x44, 0xad);
+
+pub const MAP_CHANGE_HIGHLIGHT_SECS: f64 = 30.0;
+/// How long the due command is flashed in the commands panel
+pub const ALARM_FLASH_SECS: f64 = 10.0;
 
 pub const BLEACH_ALPHA: u8 = 166;
 
 pub const ARROW_WIDTH: f32 = 5.0;
 pub const ARROW_TIP_CIRCLE: f32 = 5.0;
+/// Angle between arrow shaft and each side of its tip
+pub const ARROW_TIP_ANGLE: f32 = std::f32::consts::TAU / 10.0;
+/// Distance between side by side arrows over the same cells
+pub const ARROW_LANE_OFFSET: f32 = 8.0;
+pub const ARROW_LEGEND_WIDTH: f32 = 24.0;
+
+pub const CELL_INPUT_WIDTH: f32 = 80.0;
+pub const CELL_INPUT_SUGGESTIONS: usize = 10;
+
+pub const TOGGLE_SOE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::S);
+pub const TOGGLE_SHQ_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::H);
+pub const TOGGLE_SFM_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, Key::F);
+pub const TOGGLE_CARAVANS_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::ALT, Key::C);
 
 pub const CARAVAN_TIME: Duration = Duration::minutes(4);
 pub const CARAVAN_TO_HOME_MONEY: u32 = 2;
@@ -0,0 +1,216 @@
+use crate::cell::CellElement;
+use crate::emoji::EmojiCode;
+use crate::grid::PoI;
+use crate::homeland::Homeland;
+use std::fmt::{Display, Formatter};
+use std::iter;
+
+/// Meaning of a single cell element
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Content {
+    PoI(PoI),
+    Owner(Homeland),
+    Level(u32),
+    Resource(EmojiCode),
+    Label(String),
+}
+
+/// What a cell contains. Right corners hold cell index, so only center and left corners are
+/// interpreted
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CellContents {
+    pub center: Option<Content>,
+    pub top_left: Option<Content>,
+    pub bottom_left: Option<Content>,
+}
+
+impl CellContents {
+    pub fn new(
+        center: &Option<CellElement>,
+        top_left: &Option<CellElement>,
+        bottom_left: &Option<CellElement>,
+    ) -> Self {
+        Self {
+            center: center.as_ref().map(|center| Content::new(center, true)),
+            top_left: top_left
+                .as_ref()
+                .map(|top_left| Content::new(top_left, false)),
+            bottom_left: bottom_left
+                .as_ref()
+                .map(|bottom_left| Content::new(bottom_left, false)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Content> {
+        iter::once(&self.center)
+            .chain(iter::once(&self.top_left))
+            .chain(iter::once(&self.bottom_left))
+            .flatten()
+    }
+
+    pub fn corners(&self) -> impl Iterator<Item = &Content> {
+        iter::once(&self.top_left)
+            .chain(iter::once(&self.bottom_left))
+            .flatten()
+    }
+
+    pub fn poi(&self) -> Option<PoI> {
+        match self.center {
+            Some(Content::PoI(poi)) => Some(poi),
+            _ => None,
+        }
+    }
+
+    pub fn owner(&self) -> Option<Homeland> {
+        self.iter().find_map(|content| match content {
+            Content::Owner(homeland) => Some(*homeland),
+            _ => None,
+        })
+    }
+
+    pub fn level(&self) -> Option<u32> {
+        self.iter().find_map(|content| match content {
+            Content::Level(level) => Some(*level),
+            _ => None,
+        })
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = EmojiCode> {
+        self.iter().filter_map(|content| match content {
+            Content::Resource(emoji_code) => Some(emoji_code.clone()),
+            _ => None,
+        })
+    }
+
+    pub fn contains_emoji(&self, emoji_code: &EmojiCode) -> bool {
+        let emoji_code = Some(emoji_code.without_variation_selector());
+        self.iter().any(|content| content.emoji() == emoji_code)
+    }
+}
+
+impl Content {
+    fn new(cell_element: &CellElement, center: bool) -> Self {
+        match cell_element {
+            CellElement::Emoji(emoji_code) => {
+                let emoji_code = emoji_code.without_variation_selector();
+                if let Some(poi) = PoI::try_from(&emoji_code).ok().filter(|_| center) {
+                    Content::PoI(poi)
+                } else if let Ok(homeland) = Homeland::try_from(&emoji_code) {
+                    Content::Owner(homeland)
+                } else {
+                    Content::Resource(emoji_code)
+                }
+            }
+            CellElement::Text(text) => parse_level(text)
+                .map(Content::Level)
+                .unwrap_or_else(|| Content::Label(text.clone())),
+        }
+    }
+
+    /// Emoji of content without variation selector, if content is drawn as an emoji
+    pub fn emoji(&self) -> Option<EmojiCode> {
+        Some(match self {
+            Content::PoI(poi) => poi.emoji(),
+            Content::Owner(homeland) => homeland.into(),
+            Content::Resource(emoji_code) => emoji_code.clone(),
+            Content::Level(_) | Content::Label(_) => return None,
+        })
+    }
+}
+
+/// Level is a number with an optional non-numeric prefix, like `3` or `lvl 3`
+fn parse_level(s: &str) -> Option<u32> {
+    s.trim_start_matches(|ch: char| !ch.is_ascii_digit())
+        .parse()
+        .ok()
+}
+
+impl PoI {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+
+    pub const fn emoji(&self) -> EmojiCode {
+        match self {
+            PoI::Campfire => EmojiCode::from_static("\u{1f525}"),
+            PoI::Fountain => EmojiCode::from_static("\u{26f2}"),
+            PoI::Forum => EmojiCode::from_static("\u{1f3db}"),
+        }
+    }
+}
+
+impl TryFrom<&EmojiCode> for PoI {
+    type Error = ();
+
+    fn try_from(value: &EmojiCode) -> Result<Self, Self::Error> {
+        Ok(match value.without_variation_selector().as_str() {
+            "\u{1f525}" => PoI::Campfire,
+            "\u{26f2}" => PoI::Fountain,
+            "\u{1f3db}" => PoI::Forum,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl Display for Content {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Level(level) => level.fmt(f),
+            Content::Label(label) => label.fmt(f),
+            _ => self.emoji().unwrap().fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_poi() {
+        let contents = CellContents::new(
+            &Some(EmojiCode::from_static("\u{26f2}\u{fe0f}").into()),
+            &None,
+            &None,
+        );
+        assert_eq!(contents.poi(), Some(PoI::Fountain));
+        assert!(contents.contains_emoji(&'\u{26f2}'.into()));
+        assert_eq!(contents.resources().count(), 0);
+    }
+
+    #[test]
+    fn corner_poi_is_resource() {
+        let contents = CellContents::new(&None, &Some(EmojiCode::from('\u{1f525}').into()), &None);
+        assert_eq!(contents.poi(), None);
+        assert_eq!(
+            contents.resources().collect::<Vec<_>>(),
+            [EmojiCode::from('\u{1f525}')]
+        );
+    }
+
+    #[test]
+    fn owner_and_level() {
+        let contents = CellContents::new(
+            &Some(EmojiCode::from('\u{1f332}').into()),
+            &Some(EmojiCode::from(Homeland::Red).into()),
+            &Some("lvl 3".to_string().into()),
+        );
+        assert_eq!(contents.owner(), Some(Homeland::Red));
+        assert_eq!(contents.level(), Some(3));
+        assert_eq!(
+            contents.resources().collect::<Vec<_>>(),
+            [EmojiCode::from('\u{1f332}')]
+        );
+        assert!(contents.contains_emoji(&Homeland::Red.into()));
+    }
+
+    #[test]
+    fn label() {
+        let contents = CellContents::new(&None, &None, &Some("abc".to_string().into()));
+        assert_eq!(
+            contents.bottom_left,
+            Some(Content::Label("abc".to_string()))
+        );
+        assert_eq!(contents.level(), None);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::index::CellIndex;
+use crate::index::{CellIndex, CellIndexCommandSuffix};
 use crate::skill::{Fleetfoot, Skill};
+use enum_map::Enum;
 use serde::{Deserialize, Serialize};
 use smallvec::{SmallVec, smallvec};
 use std::cmp::Ordering;
@@ -110,7 +111,39 @@ pub enum AggregatedCost {
     },
 }
 
+/// Kind of route step, as it is drawn on the map
+#[derive(
+    Eq, PartialEq, Copy, Clone, Debug, EnumIter, IntoStaticStr, Enum, Serialize, Deserialize,
+)]
+pub enum MoveKind {
+    CentralMove,
+    StandardMove,
+    Caravan,
+    ScrollOfEscape,
+    ScrollOfEscapeHQ,
+    ScrollOfEscapeForum,
+}
+
+impl MoveKind {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
 impl AggregatedCost {
+    /// `None` for [`AggregatedCost::NoMove`]
+    pub const fn kind(&self) -> Option<MoveKind> {
+        Some(match self {
+            AggregatedCost::NoMove => return None,
+            AggregatedCost::CentralMove { .. } => MoveKind::CentralMove,
+            AggregatedCost::StandardMove { .. } => MoveKind::StandardMove,
+            AggregatedCost::Caravan(_) => MoveKind::Caravan,
+            AggregatedCost::ScrollOfEscape { .. } => MoveKind::ScrollOfEscape,
+            AggregatedCost::ScrollOfEscapeHQ { .. } => MoveKind::ScrollOfEscapeHQ,
+            AggregatedCost::ScrollOfEscapeForum { .. } => MoveKind::ScrollOfEscapeForum,
+        })
+    }
+
     pub fn time(&self) -> Duration {
         match self {
             AggregatedCost::ScrollOfEscape { .. }
@@ -150,6 +183,22 @@ impl AggregatedCost {
     }
 }
 
+impl Command {
+    /// Bot command to make the step, `None` for [`AggregatedCost::NoMove`]
+    pub fn text(&self) -> Option<String> {
+        Some(match self.aggregated_cost {
+            AggregatedCost::NoMove => return None,
+            AggregatedCost::CentralMove { .. } | AggregatedCost::StandardMove { .. } => {
+                format!("/go_direct_{}", CellIndexCommandSuffix(self.to))
+            }
+            AggregatedCost::Caravan(_) => format!("/car_{}", CellIndexCommandSuffix(self.to)),
+            AggregatedCost::ScrollOfEscape { .. } => "/use_soe".to_string(),
+            AggregatedCost::ScrollOfEscapeHQ { .. } => "/use_shq".to_string(),
+            AggregatedCost::ScrollOfEscapeForum { .. } => "/use_sfm".to_string(),
+        })
+    }
+}
+
 impl From<(EdgeCost, u32, u32, u32, Fleetfoot)> for AggregatedCost {
     fn from(
         (
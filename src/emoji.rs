@@ -1,41 +1,158 @@
-use crate::consts::{EMOJI_CORNER_SIZE, FONT_CENTER_SIZE};
+use crate::consts::EMOJI_TEXTURE_STEP;
 use eframe::epaint::textures::TextureOptions;
 use eframe::epaint::{ColorImage, TextureHandle};
-use egui::Vec2;
 use resvg::usvg::{Options, Transform, Tree};
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde::{Deserializer, Serializer};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-use tiny_skia::{IntSize, Pixmap};
+use std::collections::hash_map::Entry;
+use std::fmt::{Display, Formatter, Write};
+use std::fs;
+use std::path::PathBuf;
+use tiny_skia::{FilterQuality, IntSize, Pixmap, PixmapPaint};
 
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Ord, PartialOrd, Debug)]
-pub struct EmojiCode(pub char, pub Option<char>);
+/// Emoji as a full grapheme sequence, including ZWJ sequences, skin tone modifiers, flags and
+/// keycaps
+#[derive(Hash, PartialEq, Eq, Clone, Ord, PartialOrd, Debug)]
+pub struct EmojiCode(Cow<'static, str>);
 
-#[derive(Clone)]
-pub struct EmojiTexture {
-    pub center: TextureHandle,
-    pub corner: TextureHandle,
+/// Emoji textures, which are rasterised on first use for the exact pixel size.
+/// Textures are dropped when `pixels_per_point` changes, e.g. when the window is moved to
+/// another screen.
+/// Emojis, which are not bundled, are loaded from the optional user directory.
+pub struct EmojiMap {
+    ctx: egui::Context,
+    /// Canonical code of each bundled emoji or alias
+    codes: HashMap<EmojiCode, EmojiCode>,
+    /// Directory with `emoji_u<hex>.svg` or `emoji_u<hex>.png` files, named like in Noto Emoji
+    dir: Option<PathBuf>,
+    /// Parsed sources, `None` marks unknown emojis, which are already logged
+    sources: RefCell<HashMap<EmojiCode, Option<EmojiSource>>>,
+    textures: RefCell<HashMap<(EmojiCode, u32), TextureHandle>>,
+    /// Pixels per point, which textures are rasterised for
+    pixels_per_point: Cell<f32>,
 }
 
-pub struct EmojiMap(HashMap<EmojiCode, EmojiTexture>);
+enum EmojiSource {
+    Svg(Box<Tree>),
+    Png(Pixmap),
+}
 
 impl EmojiMap {
     pub fn new(ctx: &egui::Context) -> Self {
-        Self(init_emojis(ctx))
+        let codes = emoji_sources()
+            .map(|(code, _)| (code.clone(), code))
+            .chain(emoji_aliases())
+            .collect();
+        Self {
+            ctx: ctx.clone(),
+            codes,
+            dir: None,
+            sources: Default::default(),
+            textures: Default::default(),
+            pixels_per_point: Cell::new(ctx.pixels_per_point()),
+        }
+    }
+
+    /// Adds directory with user supplied emojis
+    pub fn with_dir(self, dir: Option<PathBuf>) -> Self {
+        Self { dir, ..self }
+    }
+
+    pub fn contains(&self, emoji_code: &EmojiCode) -> bool {
+        self.codes.contains_key(emoji_code)
     }
 
-    pub fn get_texture(&self, emoji_code: &EmojiCode) -> Option<&EmojiTexture> {
-        self.0.get(emoji_code)
+    /// Texture of emoji, which is shown `size` points wide
+    pub fn texture(&self, emoji_code: &EmojiCode, size: f32) -> Option<TextureHandle> {
+        // Fully qualified sequences fall back to the bundled unqualified ones
+        let emoji_code = self
+            .codes
+            .get(emoji_code)
+            .or_else(|| self.codes.get(&emoji_code.without_variation_selector()))
+            .cloned()
+            .unwrap_or_else(|| emoji_code.without_variation_selector());
+        let pixels_per_point = self.ctx.pixels_per_point();
+        if pixels_per_point != self.pixels_per_point.replace(pixels_per_point) {
+            self.textures.borrow_mut().clear();
+        }
+        let width = texture_width(size * pixels_per_point);
+        let mut textures = self.textures.borrow_mut();
+        if let Some(texture) = textures.get(&(emoji_code.clone(), width)) {
+            return Some(texture.clone());
+        }
+
+        let mut sources = self.sources.borrow_mut();
+        let source = match sources.entry(emoji_code.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.load(&emoji_code)),
+        }
+        .as_ref()?;
+        let name = format!("{emoji_code}|{width}");
+        let texture = match source {
+            EmojiSource::Svg(tree) => svg_to_texture(&self.ctx, name, tree, width),
+            EmojiSource::Png(pixmap) => png_to_texture(&self.ctx, name, pixmap, width),
+        };
+        textures.insert((emoji_code, width), texture.clone());
+        Some(texture)
+    }
+
+    fn load(&self, emoji_code: &EmojiCode) -> Option<EmojiSource> {
+        let source = emoji_svg(emoji_code)
+            .and_then(|content| Tree::from_data(content, &Options::default()).ok())
+            .map(|tree| EmojiSource::Svg(Box::new(tree)))
+            .or_else(|| self.load_from_dir(emoji_code));
+        if source.is_none() {
+            log::warn!(
+                "Unknown emoji {emoji_code} ({})",
+                emoji_file_stem(emoji_code)
+            );
+        }
+        source
+    }
+
+    fn load_from_dir(&self, emoji_code: &EmojiCode) -> Option<EmojiSource> {
+        let path = self.dir.as_ref()?.join(emoji_file_stem(emoji_code));
+        if let Ok(content) = fs::read(path.with_extension("svg")) {
+            match Tree::from_data(&content, &Options::default()) {
+                Ok(tree) => return Some(EmojiSource::Svg(Box::new(tree))),
+                Err(err) => log::warn!("Can not parse {}: {err}", path.display()),
+            }
+        }
+        if let Ok(content) = fs::read(path.with_extension("png")) {
+            match Pixmap::decode_png(&content) {
+                Ok(pixmap) => return Some(EmojiSource::Png(pixmap)),
+                Err(err) => log::warn!("Can not parse {}: {err}", path.display()),
+            }
+        }
+        None
     }
 }
 
-impl EmojiTexture {
-    pub const fn get(&self, large: bool) -> (&TextureHandle, Vec2) {
-        if large {
-            (&self.center, Vec2::splat(FONT_CENTER_SIZE))
-        } else {
-            (&self.corner, Vec2::splat(EMOJI_CORNER_SIZE))
+/// File name without extension, like `emoji_u1f468_200d_1f33e`. Variation selectors are omitted,
+/// like in Noto Emoji
+fn emoji_file_stem(emoji_code: &EmojiCode) -> String {
+    let mut stem = "emoji_u".to_string();
+    for (i, ch) in emoji_code
+        .as_str()
+        .chars()
+        .filter(|&ch| ch != '\u{fe0f}')
+        .enumerate()
+    {
+        if i > 0 {
+            stem.push('_');
         }
+        write!(stem, "{:x}", ch as u32).unwrap();
     }
+    stem
+}
+
+/// Texture width in pixels is rounded up, so zooming doesn't rasterise emoji on every frame
+fn texture_width(pixels: f32) -> u32 {
+    (pixels.max(1.0).ceil() as u32).next_multiple_of(EMOJI_TEXTURE_STEP)
 }
 
 fn svg_to_texture(
@@ -48,7 +165,6 @@ fn svg_to_texture(
     let size = svg_size
         .to_int_size()
         .scale_to_width(width)
-        .and_then(|s| s.scale_by(ctx.pixels_per_point()))
         .unwrap_or_else(|| IntSize::from_wh(width, width).unwrap());
     let transform = Transform::from_scale(
         size.width() as f32 / svg_size.width(),
@@ -56,6 +172,42 @@ fn svg_to_texture(
     );
     let mut pixmap = Pixmap::new(size.width(), size.height()).unwrap();
     resvg::render(tree, transform, &mut pixmap.as_mut());
+    pixmap_to_texture(ctx, name, &pixmap)
+}
+
+fn png_to_texture(
+    ctx: &egui::Context,
+    name: impl Into<String>,
+    source: &Pixmap,
+    width: u32,
+) -> TextureHandle {
+    let size = IntSize::from_wh(source.width(), source.height())
+        .and_then(|size| size.scale_to_width(width))
+        .unwrap_or_else(|| IntSize::from_wh(width, width).unwrap());
+    let transform = Transform::from_scale(
+        size.width() as f32 / source.width() as f32,
+        size.height() as f32 / source.height() as f32,
+    );
+    let mut pixmap = Pixmap::new(size.width(), size.height()).unwrap();
+    pixmap.draw_pixmap(
+        0,
+        0,
+        source.as_ref(),
+        &PixmapPaint {
+            quality: FilterQuality::Bicubic,
+            ..Default::default()
+        },
+        transform,
+        None,
+    );
+    pixmap_to_texture(ctx, name, &pixmap)
+}
+
+fn pixmap_to_texture(
+    ctx: &egui::Context,
+    name: impl Into<String>,
+    pixmap: &Pixmap,
+) -> TextureHandle {
     let image = ColorImage::from_rgba_premultiplied(
         [pixmap.width() as _, pixmap.height() as _],
         pixmap.data(),
@@ -64,138 +216,221 @@ fn svg_to_texture(
 }
 
 macro_rules! char_to_emoji_map {
-    [$(($ch:expr, $path:expr)),* $(,)?] => {
+    [$(($code:expr, $path:expr)),* $(,)?] => {
         [$((
-            EmojiCode::from($ch),
+            EmojiCode::from_static($code),
             include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/images/", $path)).as_ref(),
         )),*]
     }
 }
 
 macro_rules! aliases_to_chars_map {
-    [$(($alias:expr, $ch:expr)),* $(,)?] => {
+    [$(($alias:expr, $code:expr)),* $(,)?] => {
         [$((
-            EmojiCode::from($alias),
-            EmojiCode::from($ch)
+            EmojiCode::from_static($alias),
+            EmojiCode::from_static($code)
         )),*]
     }
 }
 
-fn init_emojis(ctx: &egui::Context) -> HashMap<EmojiCode, EmojiTexture> {
-    let char_to_emoji_map = char_to_emoji_map![
-        (('\u{1f1ea}', '\u{1f1fa}'), "emoji_u1f1ea_1f1fa.svg"),
-        (('\u{1f1ee}', '\u{1f1f2}'), "emoji_u1f1ee_1f1f2.svg"),
-        (('\u{1f1f2}', '\u{1f1f4}'), "emoji_u1f1f2_1f1f4.svg"),
-        (('\u{1f1fb}', '\u{1f1e6}'), "emoji_u1f1fb_1f1e6.svg"),
-        ('\u{1f332}', "emoji_u1f332.svg"),
-        ('\u{1f333}', "emoji_u1f333.svg"),
-        ('\u{1f338}', "emoji_u1f338.svg"),
-        ('\u{1f33b}', "emoji_u1f33b.svg"),
-        ('\u{1f33d}', "emoji_u1f33d.svg"),
-        ('\u{1f33e}', "emoji_u1f33e.svg"),
-        ('\u{1f344}', "emoji_u1f344.svg"),
-        ('\u{1f347}', "emoji_u1f347.svg"),
-        ('\u{1f34f}', "emoji_u1f34f.svg"),
-        ('\u{1f356}', "emoji_u1f356.svg"),
-        ('\u{1f3d4}', "emoji_u1f3d4.svg"),
-        ('\u{1f3db}', "emoji_u1f3db.svg"),
-        ('\u{1f3df}', "emoji_u1f3df.svg"),
-        ('\u{1f3f0}', "emoji_u1f3f0.svg"),
-        ('\u{1f410}', "emoji_u1f410.svg"),
-        ('\u{1f411}', "emoji_u1f411.svg"),
-        ('\u{1f414}', "emoji_u1f414.svg"),
-        ('\u{1f417}', "emoji_u1f417.svg"),
-        ('\u{1f41a}', "emoji_u1f41a.svg"),
-        ('\u{1f41d}', "emoji_u1f41d.svg"),
-        ('\u{1f41f}', "emoji_u1f41f.svg"),
-        ('\u{1f43f}', "emoji_u1f43f.svg"),
-        ('\u{1f463}', "emoji_u1f463.svg"),
-        ('\u{1f48e}', "emoji_u1f48e.svg"),
-        ('\u{1f4ab}', "emoji_u1f4ab.svg"),
-        ('\u{1f4cd}', "emoji_u1f4cd.svg"),
-        ('\u{1f525}', "emoji_u1f525.svg"),
-        ('\u{1f573}', "emoji_u1f573.svg"),
-        ('\u{1f578}', "emoji_u1f578.svg"),
-        ('\u{1f5fc}', "emoji_u1f5fc.svg"),
-        ('\u{1f5ff}', "emoji_u1f5ff.svg"),
-        ('\u{1f6d6}', "emoji_u1f6d6.svg"),
-        ('\u{1f6e1}', "emoji_u1f6e1.svg"),
-        ('\u{1f986}', "emoji_u1f986.svg"),
-        ('\u{1f987}', "emoji_u1f987.svg"),
-        ('\u{1f98b}', "emoji_u1f98b.svg"),
-        ('\u{1f98c}', "emoji_u1f98c.svg"),
-        ('\u{1f9b4}', "emoji_u1f9b4.svg"),
-        ('\u{1f9f1}', "emoji_u1f9f1.svg"),
-        ('\u{1fa99}', "emoji_u1fa99.svg"),
-        ('\u{1faa8}', "emoji_u1faa8.svg"),
-        ('\u{1fab5}', "emoji_u1fab5.svg"),
-        ('\u{23f0}', "emoji_u23f0.svg"),
-        ('\u{2694}', "emoji_u2694.svg"),
-        ('\u{26f2}', "emoji_u26f2.svg"),
-        ('\u{26fa}', "emoji_u26fa.svg"),
-        ('\u{2728}', "emoji_u2728.svg"),
-    ];
-    let aliases = aliases_to_chars_map![
-        (('\u{1f3db}', '\u{fe0f}'), '\u{1f3db}'),
-        (('\u{1f43f}', '\u{fe0f}'), '\u{1f43f}'),
-        (('\u{1f6e1}', '\u{fe0f}'), '\u{1f6e1}'),
-        (('\u{2694}', '\u{fe0f}'), '\u{2694}'),
-        (('\u{26fa}', '\u{fe0f}'), '\u{26fa}'),
-        (('\u{26f2}', '\u{fe0f}'), '\u{26f2}'),
-    ];
-
-    let mut map = HashMap::with_capacity(char_to_emoji_map.len() + aliases.len());
-
-    map.extend(char_to_emoji_map.into_iter().map(|(ch, content)| {
-        let rtree = Tree::from_data(content, &Options::default()).unwrap();
-        let svg_to_texture =
-            |ctx, width| svg_to_texture(ctx, format!("{ch}|{width}"), &rtree, width);
-        (
-            ch,
-            EmojiTexture {
-                center: svg_to_texture(ctx, FONT_CENTER_SIZE as _),
-                corner: svg_to_texture(ctx, EMOJI_CORNER_SIZE as _),
-            },
-        )
-    }));
+/// Bundled SVG sources of emojis
+fn emoji_sources() -> impl Iterator<Item = (EmojiCode, &'static [u8])> {
+    char_to_emoji_map![
+        ("\u{1f1ea}\u{1f1fa}", "emoji_u1f1ea_1f1fa.svg"),
+        ("\u{1f1ee}\u{1f1f2}", "emoji_u1f1ee_1f1f2.svg"),
+        ("\u{1f1f2}\u{1f1f4}", "emoji_u1f1f2_1f1f4.svg"),
+        ("\u{1f1fb}\u{1f1e6}", "emoji_u1f1fb_1f1e6.svg"),
+        ("\u{1f332}", "emoji_u1f332.svg"),
+        ("\u{1f333}", "emoji_u1f333.svg"),
+        ("\u{1f338}", "emoji_u1f338.svg"),
+        ("\u{1f33b}", "emoji_u1f33b.svg"),
+        ("\u{1f33d}", "emoji_u1f33d.svg"),
+        ("\u{1f33e}", "emoji_u1f33e.svg"),
+        ("\u{1f344}", "emoji_u1f344.svg"),
+        ("\u{1f347}", "emoji_u1f347.svg"),
+        ("\u{1f34f}", "emoji_u1f34f.svg"),
+        ("\u{1f356}", "emoji_u1f356.svg"),
+        ("\u{1f3d4}", "emoji_u1f3d4.svg"),
+        ("\u{1f3db}", "emoji_u1f3db.svg"),
+        ("\u{1f3df}", "emoji_u1f3df.svg"),
+        ("\u{1f3f0}", "emoji_u1f3f0.svg"),
+        ("\u{1f410}", "emoji_u1f410.svg"),
+        ("\u{1f411}", "emoji_u1f411.svg"),
+        ("\u{1f414}", "emoji_u1f414.svg"),
+        ("\u{1f417}", "emoji_u1f417.svg"),
+        ("\u{1f41a}", "emoji_u1f41a.svg"),
+        ("\u{1f41d}", "emoji_u1f41d.svg"),
+        ("\u{1f41f}", "emoji_u1f41f.svg"),
+        ("\u{1f43f}", "emoji_u1f43f.svg"),
+        ("\u{1f463}", "emoji_u1f463.svg"),
+        ("\u{1f48e}", "emoji_u1f48e.svg"),
+        ("\u{1f4ab}", "emoji_u1f4ab.svg"),
+        ("\u{1f4cd}", "emoji_u1f4cd.svg"),
+        ("\u{1f525}", "emoji_u1f525.svg"),
+        ("\u{1f573}", "emoji_u1f573.svg"),
+        ("\u{1f578}", "emoji_u1f578.svg"),
+        ("\u{1f5fc}", "emoji_u1f5fc.svg"),
+        ("\u{1f5ff}", "emoji_u1f5ff.svg"),
+        ("\u{1f6d6}", "emoji_u1f6d6.svg"),
+        ("\u{1f6e1}", "emoji_u1f6e1.svg"),
+        ("\u{1f986}", "emoji_u1f986.svg"),
+        ("\u{1f987}", "emoji_u1f987.svg"),
+        ("\u{1f98b}", "emoji_u1f98b.svg"),
+        ("\u{1f98c}", "emoji_u1f98c.svg"),
+        ("\u{1f9b4}", "emoji_u1f9b4.svg"),
+        ("\u{1f9f1}", "emoji_u1f9f1.svg"),
+        ("\u{1fa99}", "emoji_u1fa99.svg"),
+        ("\u{1faa8}", "emoji_u1faa8.svg"),
+        ("\u{1fab5}", "emoji_u1fab5.svg"),
+        ("\u{23f0}", "emoji_u23f0.svg"),
+        ("\u{2694}", "emoji_u2694.svg"),
+        ("\u{26f2}", "emoji_u26f2.svg"),
+        ("\u{26fa}", "emoji_u26fa.svg"),
+        ("\u{2728}", "emoji_u2728.svg"),
+    ]
+    .into_iter()
+}
 
-    let aliases = aliases.map(|(from, to)| (from, map[&to].clone()));
-    map.extend(aliases);
+/// Alternative codes of bundled emojis
+fn emoji_aliases() -> impl Iterator<Item = (EmojiCode, EmojiCode)> {
+    aliases_to_chars_map![
+        ("\u{1f3db}\u{fe0f}", "\u{1f3db}"),
+        ("\u{1f43f}\u{fe0f}", "\u{1f43f}"),
+        ("\u{1f6e1}\u{fe0f}", "\u{1f6e1}"),
+        ("\u{2694}\u{fe0f}", "\u{2694}"),
+        ("\u{26fa}\u{fe0f}", "\u{26fa}"),
+        ("\u{26f2}\u{fe0f}", "\u{26f2}"),
+    ]
+    .into_iter()
+}
 
-    map
+/// SVG source of bundled emoji, which doesn't need egui context
+pub fn emoji_svg(emoji_code: &EmojiCode) -> Option<&'static [u8]> {
+    let find = |emoji_code: &EmojiCode| {
+        let emoji_code = emoji_aliases()
+            .find_map(|(alias, code)| (alias == *emoji_code).then_some(code))
+            .unwrap_or_else(|| emoji_code.clone());
+        emoji_sources().find_map(|(code, content)| (code == emoji_code).then_some(content))
+    };
+    find(emoji_code).or_else(|| find(&emoji_code.without_variation_selector()))
 }
 
-impl From<char> for EmojiCode {
-    fn from(c0: char) -> Self {
-        Self(c0, None)
+impl EmojiCode {
+    pub const fn from_static(code: &'static str) -> Self {
+        Self(Cow::Borrowed(code))
+    }
+
+    /// Emoji of the whole string, if it is a single emoji sequence
+    pub fn parse(s: &str) -> Option<Self> {
+        is_emoji_sequence(s).then(|| Self(Cow::Owned(s.to_string())))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn without_variation_selector(&self) -> Self {
+        if self.0.contains('\u{fe0f}') {
+            Self(Cow::Owned(self.0.replace('\u{fe0f}', "")))
+        } else {
+            self.clone()
+        }
     }
 }
 
-impl From<(char, char)> for EmojiCode {
-    fn from((c0, c1): (char, char)) -> Self {
-        Self(c0, Some(c1))
+/// Whether string is a single emoji: a pictograph with optional modifiers, variation selectors
+/// and ZWJ joined pictographs, a flag or a keycap
+fn is_emoji_sequence(s: &str) -> bool {
+    let mut chars = s.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    let rest = chars.as_str();
+    if matches!(first, '0'..='9' | '#' | '*') {
+        return matches!(rest, "\u{20e3}" | "\u{fe0f}\u{20e3}");
     }
+    is_pictograph(first)
+        && rest
+            .chars()
+            .all(|ch| is_pictograph(ch) || is_emoji_component(ch))
+}
+
+fn is_pictograph(ch: char) -> bool {
+    matches!(ch,
+        '\u{a9}' | '\u{ae}' | '\u{203c}' | '\u{2049}' | '\u{2122}' | '\u{2139}'
+        | '\u{2194}'..='\u{21aa}' | '\u{231a}'..='\u{23ff}' | '\u{24c2}'
+        | '\u{25aa}'..='\u{27bf}' | '\u{2934}' | '\u{2935}' | '\u{2b05}'..='\u{2b55}'
+        | '\u{3030}' | '\u{303d}' | '\u{3297}' | '\u{3299}'
+        // Regional indicators, skin tones and the most of pictographs
+        | '\u{1f000}'..='\u{1faff}')
+}
+
+/// Parts of sequences, which aren't emojis on their own
+fn is_emoji_component(ch: char) -> bool {
+    matches!(
+        ch,
+        // Zero width joiner, variation selector, keycap
+        '\u{200d}' | '\u{fe0f}' | '\u{20e3}'
+        // Tags of subdivision flags
+        | '\u{e0020}'..='\u{e007f}'
+    )
 }
 
-impl TryFrom<&[char]> for EmojiCode {
-    type Error = ();
+impl From<char> for EmojiCode {
+    fn from(ch: char) -> Self {
+        Self(Cow::Owned(ch.to_string()))
+    }
+}
 
-    fn try_from(value: &[char]) -> Result<Self, Self::Error> {
-        Ok(match value {
-            [c0] => (*c0).into(),
-            [c0, c1] => (*c0, *c1).into(),
-            _ => return Err(()),
-        })
+impl From<String> for EmojiCode {
+    fn from(code: String) -> Self {
+        Self(Cow::Owned(code))
     }
 }
 
 impl Display for EmojiCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)?;
-        if let Some(c1) = self.1 {
-            c1.fmt(f)?;
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for EmojiCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Also reads `(char, Option<char>)` pairs, which were stored by earlier versions
+impl<'de> Deserialize<'de> for EmojiCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EmojiCodeVisitor;
+
+        impl<'de> Visitor<'de> for EmojiCodeVisitor {
+            type Value = EmojiCode;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("emoji string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(v.to_string().into())
+            }
+
+            fn visit_char<E: serde::de::Error>(self, v: char) -> Result<Self::Value, E> {
+                Ok(v.into())
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut code = String::new();
+                code.extend(seq.next_element::<char>()?);
+                while let Some(ch) = seq.next_element::<Option<char>>()? {
+                    code.extend(ch);
+                }
+                Ok(code.into())
+            }
         }
-        Ok(())
+
+        deserializer.deserialize_any(EmojiCodeVisitor)
     }
 }
 
@@ -206,7 +441,7 @@ mod tests {
 
     #[test]
     fn emoji_code_2_display() {
-        let emoji_code = EmojiCode::from(('\u{26f2}', '\u{fe0f}'));
+        let emoji_code = EmojiCode::from_static("\u{26f2}\u{fe0f}");
         let emoji_str = emoji_code.to_string();
         assert_eq!(emoji_str, "\u{26f2}\u{fe0f}");
     }
@@ -219,12 +454,112 @@ mod tests {
     }
 
     #[test]
-    fn init_emojis_get() {
+    fn emoji_code_parse() {
+        for emoji in [
+            "\u{26f2}",
+            "\u{26f2}\u{fe0f}",
+            "\u{1f1ea}\u{1f1fa}",
+            // Farmer: man, medium skin tone, ZWJ, sheaf of rice
+            "\u{1f468}\u{1f3fd}\u{200d}\u{1f33e}",
+            // Family of four
+            "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}",
+            // Keycap 1
+            "1\u{fe0f}\u{20e3}",
+            // Flag of Scotland
+            "\u{1f3f4}\u{e0067}\u{e0062}\u{e0073}\u{e0063}\u{e0074}\u{e007f}",
+        ] {
+            assert_eq!(
+                EmojiCode::parse(emoji).map(|code| code.to_string()),
+                Some(emoji.to_string())
+            );
+        }
+        for text in ["", "B", "12", "2#2", "lvl 3", "\u{1f525} 3", "\u{200d}"] {
+            assert_eq!(EmojiCode::parse(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn emoji_code_legacy_deserialize() {
+        let legacy: EmojiCode = ron::from_str("('\u{26f2}', Some('\u{fe0f}'))").unwrap();
+        assert_eq!(legacy, EmojiCode::from_static("\u{26f2}\u{fe0f}"));
+        let legacy: EmojiCode = ron::from_str("('\u{26f2}', None)").unwrap();
+        assert_eq!(legacy, EmojiCode::from('\u{26f2}'));
+        let code = EmojiCode::from_static("\u{1f468}\u{200d}\u{1f33e}");
+        let stored = ron::to_string(&code).unwrap();
+        assert_eq!(ron::from_str::<EmojiCode>(&stored).unwrap(), code);
+    }
+
+    #[test]
+    fn texture_cache() {
+        let ctx = Context::default();
+        let emojis = EmojiMap::new(&ctx);
+        let texture = |emoji_code: &'static str, size| {
+            emojis
+                .texture(&EmojiCode::from_static(emoji_code), size)
+                .unwrap()
+        };
+        let shield = texture("\u{1f6e1}", 16.0);
+        assert_eq!(shield.size(), [16, 16]);
+        assert_eq!(texture("\u{1f6e1}\u{fe0f}", 16.0).id(), shield.id());
+        assert_eq!(texture("\u{1f6e1}", 15.5).id(), shield.id());
+        assert_ne!(texture("\u{1f6e1}", 32.0).id(), shield.id());
+        assert!(emojis.texture(&'a'.into(), 16.0).is_none());
+
+        ctx.set_zoom_factor(2.0);
+        let _ = ctx.run(Default::default(), |_| {});
+        let hidpi = texture("\u{1f6e1}", 16.0);
+        assert_ne!(hidpi.id(), shield.id());
+        assert_eq!(hidpi.size(), [32, 32]);
+        assert_eq!(emojis.textures.borrow().len(), 1);
+    }
+
+    #[test]
+    fn emoji_dir_fallback() {
+        let dir = std::env::temp_dir().join(format!("marshrutka-emojis-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <rect width="32" height="32" fill="red"/></svg>"#;
+        fs::write(dir.join("emoji_u1f344.svg"), svg).unwrap();
+        let mut pixmap = Pixmap::new(4, 2).unwrap();
+        pixmap.fill(tiny_skia::Color::WHITE);
+        fs::write(
+            dir.join("emoji_u1f468_200d_1f33e.png"),
+            pixmap.encode_png().unwrap(),
+        )
+        .unwrap();
+
         let ctx = Context::default();
-        let emojis = init_emojis(&ctx);
+        let emojis = EmojiMap::new(&ctx).with_dir(Some(dir.clone()));
+        let mushroom = EmojiCode::from_static("\u{1f344}\u{fe0f}");
+        assert_eq!(emojis.texture(&mushroom, 16.0).unwrap().size(), [16, 16]);
+        let farmer = EmojiCode::from_static("\u{1f468}\u{200d}\u{1f33e}");
+        assert_eq!(emojis.texture(&farmer, 16.0).unwrap().size(), [16, 8]);
+        assert!(emojis.texture(&'\u{1f955}'.into(), 16.0).is_none());
+        assert!(emojis.texture(&'\u{1f955}'.into(), 32.0).is_none());
+        // Unknown emoji is looked up and logged once
+        assert!(emojis.sources.borrow()[&'\u{1f955}'.into()].is_none());
+        assert_eq!(emojis.sources.borrow().len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn emoji_file_stems() {
+        assert_eq!(emoji_file_stem(&'\u{1f344}'.into()), "emoji_u1f344");
+        assert_eq!(
+            emoji_file_stem(&EmojiCode::from_static(
+                "\u{1f3f3}\u{fe0f}\u{200d}\u{1f308}"
+            )),
+            "emoji_u1f3f3_200d_1f308"
+        );
+    }
+
+    #[test]
+    fn emoji_svg_alias() {
         assert_eq!(
-            emojis[&('\u{1f6e1}', '\u{fe0f}').into()].center.id(),
-            emojis[&'\u{1f6e1}'.into()].center.id()
+            emoji_svg(&EmojiCode::from_static("\u{26fa}\u{fe0f}")),
+            emoji_svg(&'\u{26fa}'.into())
         );
+        assert!(emoji_svg(&'\u{26fa}'.into()).is_some());
+        assert!(emoji_svg(&'a'.into()).is_none());
     }
 }
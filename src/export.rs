@@ -0,0 +1,585 @@
+use crate::cell::CellElement;
+use crate::consts::{
+    ARROW_TIP_ANGLE, ARROW_TIP_CIRCLE, ARROW_WIDTH, BLEACH_ALPHA, CELL_MARGIN, CELL_ROUNDING,
+    CELL_SIZE, EMOJI_CORNER_SIZE, FONT_CENTER_SIZE, FONT_CORNER_SIZE, GRID_SPACING,
+};
+use crate::cost::{Command, MoveKind};
+use crate::emoji::{EmojiCode, emoji_svg};
+use crate::grid::{ArrowLanes, MapGrid};
+use crate::index::CellIndex;
+use crate::theme::Theme;
+use ab_glyph::{Font, FontArc, OutlineCurve, ScaleFont};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use egui::emath::Rot2;
+use egui::{Align2, Color32, FontDefinitions, FontFamily, Pos2, Rect, Vec2};
+use resvg::usvg::{Options, Transform, Tree};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+use std::path::Path;
+use tiny_skia::Pixmap;
+
+const TEXT_COLOR: Color32 = Color32::from_rgb(0x2c, 0x3e, 0x50);
+
+/// Renders map with a route into an image file without egui window
+///
+/// ```
+/// use marshrutka::export::MapExport;
+/// use marshrutka::grid::MapGridBuilder;
+/// use marshrutka::index::CellIndex;
+///
+/// let grid = MapGridBuilder::new(2).build().unwrap();
+/// let route = ["B2#2", "B1#1", "0#0"].map(|cell| CellIndex::parse_any(cell).unwrap());
+/// let export = MapExport::new(&grid).route(&route).scale(2.0);
+/// assert_eq!(export.to_svg().matches("<g ").count(), 2);
+/// assert_eq!(&export.to_png().unwrap()[1..4], b"PNG");
+/// ```
+pub struct MapExport<'a> {
+    grid: &'a MapGrid,
+    route: Vec<(MoveKind, CellIndex, CellIndex)>,
+    theme: Theme,
+    scale: f32,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Svg(resvg::usvg::Error),
+    /// Image is empty or too large to allocate
+    Size(f32, f32),
+    Png(String),
+    Io(std::io::Error),
+    UnsupportedFormat(String),
+}
+
+/// Map in image coordinates, before scaling
+enum Shape {
+    Cell {
+        rect: Rect,
+        color: Color32,
+    },
+    Emoji {
+        emoji_code: EmojiCode,
+        rect: Rect,
+        alpha: u8,
+    },
+    Text {
+        text: String,
+        /// Left end of baseline
+        pos: Pos2,
+        size: f32,
+        alpha: u8,
+    },
+    Arrow {
+        from: Pos2,
+        to: Pos2,
+        color: Color32,
+    },
+}
+
+/// Proportional egui fonts, the first one having the glyph is used
+struct Fonts(Vec<FontArc>);
+
+impl<'a> MapExport<'a> {
+    pub fn new(grid: &'a MapGrid) -> Self {
+        Self {
+            grid,
+            route: Vec::new(),
+            theme: Theme::default(),
+            scale: 1.0,
+        }
+    }
+
+    /// Route through the cells in order. Moves from or to the center are drawn as central moves,
+    /// others as standard moves.
+    pub fn route(mut self, path: &[CellIndex]) -> Self {
+        self.route = path
+            .windows(2)
+            .map(|pair| {
+                let kind = if pair.contains(&CellIndex::Center) {
+                    MoveKind::CentralMove
+                } else {
+                    MoveKind::StandardMove
+                };
+                (kind, pair[0], pair[1])
+            })
+            .collect();
+        self
+    }
+
+    /// Route as it is found, with the actual kind of every move
+    pub(crate) fn commands(mut self, commands: &[Command]) -> Self {
+        self.route = commands
+            .iter()
+            .filter_map(|command| Some((command.aggregated_cost.kind()?, command.from, command.to)))
+            .collect();
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Pixels per point of the image
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Size of the image in points
+    pub fn size(&self) -> Vec2 {
+        let side = self.grid.square_size as f32 * (CELL_SIZE + GRID_SPACING) - GRID_SPACING;
+        Vec2::splat(side.max(0.0))
+    }
+
+    pub fn to_svg(&self) -> String {
+        self.svg(&Fonts::new(), false)
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, ExportError> {
+        // Text is converted to glyph outlines, as `resvg` is built without fonts support
+        let svg = self.svg(&Fonts::new(), true);
+        let tree = Tree::from_str(&svg, &Options::default()).map_err(ExportError::Svg)?;
+        let size = self.size() * self.scale;
+        let mut pixmap = Pixmap::new(size.x.ceil() as _, size.y.ceil() as _)
+            .ok_or(ExportError::Size(size.x, size.y))?;
+        resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+        pixmap
+            .encode_png()
+            .map_err(|e| ExportError::Png(e.to_string()))
+    }
+
+    /// Writes PNG or SVG file, depending on extension of `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ExportError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let content = self.encode(&extension)?;
+        std::fs::write(path, content).map_err(ExportError::Io)
+    }
+
+    /// PNG or SVG image, `extension` is the lowercase file extension of the format
+    pub fn encode(&self, extension: &str) -> Result<Vec<u8>, ExportError> {
+        match extension {
+            "png" => self.to_png(),
+            "svg" => Ok(self.to_svg().into_bytes()),
+            _ => Err(ExportError::UnsupportedFormat(extension.to_string())),
+        }
+    }
+
+    fn shapes(&self, fonts: &Fonts) -> Vec<Shape> {
+        let step = CELL_SIZE + GRID_SPACING;
+        let mut shapes = Vec::new();
+        let mut centers = HashMap::with_capacity(self.grid.grid.len());
+        for (i, cell) in self.grid.grid.iter().enumerate() {
+            let (col, row) = (i % self.grid.square_size, i / self.grid.square_size);
+            let cell_rect = Rect::from_min_size(
+                Pos2::new(col as f32 * step, row as f32 * step),
+                Vec2::splat(CELL_SIZE),
+            );
+            centers.insert(cell.index, cell_rect.center());
+            if let Some(color) = cell.bg_color {
+                shapes.push(Shape::Cell {
+                    rect: cell_rect,
+                    color,
+                });
+            }
+            let rect = cell_rect.shrink(CELL_MARGIN);
+            for (cell_element, align, large) in [
+                (&cell.center, Align2::CENTER_CENTER, true),
+                (&cell.top_left, Align2::LEFT_TOP, false),
+                (&cell.top_right, Align2::RIGHT_TOP, false),
+                (&cell.bottom_left, Align2::LEFT_BOTTOM, false),
+                (&cell.bottom_right, Align2::RIGHT_BOTTOM, false),
+            ] {
+                let alpha = if large { BLEACH_ALPHA } else { 255 };
+                let text = match cell_element {
+                    None => continue,
+                    Some(CellElement::Emoji(emoji_code)) if emoji_svg(emoji_code).is_some() => {
+                        let size = if large {
+                            FONT_CENTER_SIZE
+                        } else {
+                            EMOJI_CORNER_SIZE
+                        };
+                        shapes.push(Shape::Emoji {
+                            emoji_code: emoji_code.clone(),
+                            rect: align.align_size_within_rect(Vec2::splat(size), rect),
+                            alpha,
+                        });
+                        continue;
+                    }
+                    Some(cell_element) => cell_element.to_string(),
+                };
+                let size = if large {
+                    FONT_CENTER_SIZE
+                } else {
+                    FONT_CORNER_SIZE
+                };
+                let (width, ascent, descent) = fonts.measure(&text, size);
+                let text_rect =
+                    align.anchor_size(align.pos_in_rect(&rect), Vec2::new(width, ascent - descent));
+                shapes.push(Shape::Text {
+                    text,
+                    pos: Pos2::new(text_rect.min.x, text_rect.min.y + ascent),
+                    size,
+                    alpha,
+                });
+            }
+        }
+
+        // Image has dark text on a transparent background, so it gets light theme colours
+        let mut lanes = ArrowLanes::default();
+        for &(kind, from, to) in &self.route {
+            if let Some((kind, from, to)) = lanes.take_move(kind, from, to, &centers, 1.0) {
+                shapes.push(Shape::Arrow {
+                    from,
+                    to,
+                    color: self
+                        .theme
+                        .move_color(kind, false)
+                        .gamma_multiply(BLEACH_ALPHA as f32 / 255.0),
+                });
+            }
+        }
+        shapes
+    }
+
+    fn svg(&self, fonts: &Fonts, outline_text: bool) -> String {
+        let shapes = self.shapes(fonts);
+        let size = self.size();
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            size.x * self.scale,
+            size.y * self.scale,
+            size.x,
+            size.y,
+        );
+
+        // Every used emoji is embedded once
+        let emojis: BTreeSet<_> = shapes
+            .iter()
+            .filter_map(|shape| match shape {
+                Shape::Emoji { emoji_code, .. } => Some(emoji_code),
+                _ => None,
+            })
+            .collect();
+        if !emojis.is_empty() {
+            svg.push_str("<defs>");
+            for emoji_code in emojis {
+                let content = emoji_svg(emoji_code).unwrap_or_default();
+                write!(
+                    svg,
+                    r#"<image id="{}" width="1" height="1" xlink:href="data:image/svg+xml;base64,{}"/>"#,
+                    emoji_id(emoji_code),
+                    STANDARD.encode(content),
+                )
+                .unwrap();
+            }
+            svg.push_str("</defs>");
+        }
+
+        for shape in shapes {
+            match shape {
+                Shape::Cell { rect, color } => write!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{CELL_ROUNDING}" {}/>"#,
+                    rect.min.x,
+                    rect.min.y,
+                    rect.width(),
+                    rect.height(),
+                    paint("fill", color),
+                ),
+                Shape::Emoji {
+                    emoji_code,
+                    rect,
+                    alpha,
+                } => write!(
+                    svg,
+                    r##"<use xlink:href="#{}" transform="translate({} {}) scale({})" opacity="{}"/>"##,
+                    emoji_id(&emoji_code),
+                    rect.min.x,
+                    rect.min.y,
+                    rect.width(),
+                    alpha as f32 / 255.0,
+                ),
+                Shape::Text {
+                    text,
+                    pos,
+                    size,
+                    alpha,
+                } => {
+                    let color = Color32::from_rgba_unmultiplied(
+                        TEXT_COLOR.r(),
+                        TEXT_COLOR.g(),
+                        TEXT_COLOR.b(),
+                        alpha,
+                    );
+                    if outline_text {
+                        write!(
+                            svg,
+                            r#"<path d="{}" {}/>"#,
+                            fonts.outline(&text, pos, size),
+                            paint("fill", color),
+                        )
+                    } else {
+                        write!(
+                            svg,
+                            r#"<text x="{}" y="{}" font-family="Ubuntu, sans-serif" font-size="{size}" {}>{}</text>"#,
+                            pos.x,
+                            pos.y,
+                            paint("fill", color),
+                            escape(&text),
+                        )
+                    }
+                }
+                Shape::Arrow { from, to, color } => {
+                    let dir = CELL_SIZE / 4.0 * (to - from).normalized();
+                    let rot = Rot2::from_angle(ARROW_TIP_ANGLE);
+                    let (left, right) = (to - rot * dir, to - rot.inverse() * dir);
+                    write!(
+                        svg,
+                        r#"<g {} stroke-width="{ARROW_WIDTH}" fill="none"><path d="M{} {}L{} {}M{} {}L{} {}L{} {}"/><circle cx="{}" cy="{}" r="{ARROW_TIP_CIRCLE}"/><circle cx="{}" cy="{}" r="{ARROW_TIP_CIRCLE}" {}/></g>"#,
+                        paint("stroke", color),
+                        from.x,
+                        from.y,
+                        to.x,
+                        to.y,
+                        left.x,
+                        left.y,
+                        to.x,
+                        to.y,
+                        right.x,
+                        right.y,
+                        from.x,
+                        from.y,
+                        to.x,
+                        to.y,
+                        paint("fill", color),
+                    )
+                }
+            }
+            .unwrap();
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+impl Fonts {
+    fn new() -> Self {
+        let definitions = FontDefinitions::default();
+        Self(
+            definitions.families[&FontFamily::Proportional]
+                .iter()
+                .filter_map(|name| {
+                    let data = definitions.font_data.get(name)?;
+                    FontArc::try_from_vec(data.font.to_vec()).ok()
+                })
+                .collect(),
+        )
+    }
+
+    fn font_for(&self, ch: char) -> Option<&FontArc> {
+        self.0
+            .iter()
+            .find(|font| font.glyph_id(ch).0 != 0)
+            .or(self.0.first())
+    }
+
+    /// Width, ascent and descent of the text line
+    fn measure(&self, text: &str, size: f32) -> (f32, f32, f32) {
+        let width = text
+            .chars()
+            .filter_map(|ch| {
+                let font = self.font_for(ch)?.as_scaled(size);
+                Some(font.h_advance(font.glyph_id(ch)))
+            })
+            .sum();
+        self.0
+            .first()
+            .map(|font| {
+                let font = font.as_scaled(size);
+                (width, font.ascent(), font.descent())
+            })
+            .unwrap_or((width, size, 0.0))
+    }
+
+    /// Path data of the text outline
+    fn outline(&self, text: &str, pos: Pos2, size: f32) -> String {
+        let mut d = String::new();
+        let mut x = pos.x;
+        for ch in text.chars() {
+            let Some(font) = self.font_for(ch) else {
+                continue;
+            };
+            let scaled = font.as_scaled(size);
+            let glyph_id = font.glyph_id(ch);
+            let (sx, sy) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+            let point = |p: ab_glyph::Point| (x + p.x * sx, pos.y - p.y * sy);
+            let mut last = None;
+            for curve in font.outline(glyph_id).map(|o| o.curves).unwrap_or_default() {
+                let (start, end) = match curve {
+                    OutlineCurve::Line(p0, p1) => (p0, p1),
+                    OutlineCurve::Quad(p0, _, p2) => (p0, p2),
+                    OutlineCurve::Cubic(p0, _, _, p3) => (p0, p3),
+                };
+                if last != Some(start) {
+                    let (x0, y0) = point(start);
+                    write!(d, "M{x0} {y0}").unwrap();
+                }
+                match curve {
+                    OutlineCurve::Line(_, p1) => {
+                        let (x1, y1) = point(p1);
+                        write!(d, "L{x1} {y1}")
+                    }
+                    OutlineCurve::Quad(_, p1, p2) => {
+                        let ((x1, y1), (x2, y2)) = (point(p1), point(p2));
+                        write!(d, "Q{x1} {y1} {x2} {y2}")
+                    }
+                    OutlineCurve::Cubic(_, p1, p2, p3) => {
+                        let ((x1, y1), (x2, y2), (x3, y3)) = (point(p1), point(p2), point(p3));
+                        write!(d, "C{x1} {y1} {x2} {y2} {x3} {y3}")
+                    }
+                }
+                .unwrap();
+                last = Some(end);
+            }
+            x += scaled.h_advance(glyph_id);
+        }
+        d
+    }
+}
+
+fn emoji_id(emoji_code: &EmojiCode) -> String {
+    emoji_code
+        .as_str()
+        .chars()
+        .fold("emoji".to_string(), |mut id, ch| {
+            write!(id, "_{:x}", ch as u32).unwrap();
+            id
+        })
+}
+
+/// Colour and opacity attributes
+fn paint(attr: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!(
+        r##"{attr}="#{r:02x}{g:02x}{b:02x}" {attr}-opacity="{}""##,
+        a as f32 / 255.0
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut acc, ch| {
+            match ch {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                _ => acc.push(ch),
+            }
+            acc
+        })
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Svg(e) => write!(f, "SVG error: {e}"),
+            ExportError::Size(width, height) => {
+                write!(f, "Image of size {width}x{height} can't be created")
+            }
+            ExportError::Png(e) => write!(f, "PNG error: {e}"),
+            ExportError::Io(e) => write!(f, "I/O error: {e}"),
+            ExportError::UnsupportedFormat(extension) => {
+                write!(f, "Unsupported image format: {extension:?}")
+            }
+        }
+    }
+}
+
+impl Error for ExportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExportError::Svg(e) => Some(e),
+            ExportError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::CostComparator;
+    use crate::cost::TotalCost;
+    use crate::grid::tests::map_html;
+    use crate::homeland::Homeland;
+    use crate::pathfinder::FindPath;
+    use crate::skill::{Fleetfoot, RouteGuru};
+
+    fn route(grid: &MapGrid) -> TotalCost {
+        FindPath {
+            scroll_of_escape_cost: 50,
+            scroll_of_escape_hq_cost: 75,
+            scroll_of_escape_forum_cost: 100,
+            use_soe: false,
+            use_sfm: false,
+            use_caravans: false,
+            hq_position: None,
+            route_guru: RouteGuru(0),
+            fleetfoot: Fleetfoot(0),
+            sort_by: (CostComparator::Legs, CostComparator::Money),
+            homeland: Homeland::Blue,
+            grid,
+        }
+        .eval(CellIndex::parse_any("B2#2").unwrap(), CellIndex::Center)
+        .unwrap()
+    }
+
+    #[test]
+    fn svg_has_cells_emojis_and_route() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let route = route(&grid);
+        let svg = MapExport::new(&grid)
+            .commands(&route.commands)
+            .scale(2.0)
+            .to_svg();
+        let size = 5.0 * (CELL_SIZE + GRID_SPACING) - GRID_SPACING;
+        assert!(svg.contains(&format!(r#"width="{}""#, size * 2.0)));
+        assert_eq!(svg.matches("<rect ").count(), 24);
+        assert_eq!(svg.matches(r#"<image id="emoji_1f525""#).count(), 1);
+        assert_eq!(svg.matches(r##"xlink:href="#emoji_1f525""##).count(), 4);
+        assert_eq!(svg.matches("<g ").count(), route.commands.len());
+        assert!(svg.contains(">2#2</text>"));
+        assert!(Tree::from_str(&svg, &Options::default()).is_ok());
+    }
+
+    #[test]
+    fn png_has_map_size() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let route = route(&grid);
+        let png = MapExport::new(&grid)
+            .commands(&route.commands)
+            .to_png()
+            .unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        let size = (5.0 * (CELL_SIZE + GRID_SPACING) - GRID_SPACING) as u32;
+        // Width and height of IHDR chunk
+        assert_eq!(png[16..20], size.to_be_bytes());
+        assert_eq!(png[20..24], size.to_be_bytes());
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        assert!(matches!(
+            MapExport::new(&grid).save("map.bmp"),
+            Err(ExportError::UnsupportedFormat(extension)) if extension == "bmp"
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+/// Asks where to save `content`, `file_name` is the default name.
+/// Returns where the file is saved or an error, `None` if the dialog is cancelled.
+pub fn save(file_name: &str, mime: &str, content: &[u8]) -> Option<Result<String, String>> {
+    save_as(file_name, mime, |_| Ok(content.to_vec()))
+}
+
+/// Same as [`save`] for content, which depends on the format of the file.
+/// `encode` gets the lowercase extension of the chosen file, which is the one of `file_name`
+/// if the user gives none.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_as(
+    file_name: &str,
+    _mime: &str,
+    encode: impl FnOnce(&str) -> Result<Vec<u8>, String>,
+) -> Option<Result<String, String>> {
+    let mut path = filtered_dialog(file_name).save_file()?;
+    if path.extension().is_none() {
+        path.set_extension(extension(file_name));
+    }
+    let saved = encode(&extension(&path.to_string_lossy())).and_then(|content| {
+        std::fs::write(&path, content)
+            .map(|()| path.display().to_string())
+            .map_err(|err| err.to_string())
+    });
+    Some(saved)
+}
+
+/// Downloads content as `file_name`, web pages have no file dialogs
+#[cfg(target_arch = "wasm32")]
+pub fn save_as(
+    file_name: &str,
+    mime: &str,
+    encode: impl FnOnce(&str) -> Result<Vec<u8>, String>,
+) -> Option<Result<String, String>> {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use eframe::wasm_bindgen::JsCast as _;
+
+    let content = match encode(&extension(file_name)) {
+        Ok(content) => content,
+        Err(err) => return Some(Err(err)),
+    };
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|element| element.dyn_into::<web_sys::HtmlAnchorElement>().ok());
+    let Some(anchor) = anchor else {
+        return Some(Err(format!("Can not download {file_name}")));
+    };
+    anchor.set_href(&format!("data:{mime};base64,{}", STANDARD.encode(content)));
+    anchor.set_download(file_name);
+    anchor.click();
+    Some(Ok(file_name.to_string()))
+}
+
+/// Asks which text file to open, files like `file_name` are listed first.
+/// Returns the file and its text, `None` if the dialog is cancelled.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_text(file_name: &str) -> Option<Result<(String, String), String>> {
+    let path = filtered_dialog(file_name).pick_file()?;
+    Some(
+        std::fs::read_to_string(&path)
+            .map(|text| (path.display().to_string(), text))
+            .map_err(|err| err.to_string()),
+    )
+}
+
+/// Dialog showing files with the extension of `file_name`
+#[cfg(not(target_arch = "wasm32"))]
+fn filtered_dialog(file_name: &str) -> rfd::FileDialog {
+    let dialog = rfd::FileDialog::new().set_file_name(file_name);
+    match extension(file_name).as_str() {
+        "" => dialog,
+        extension => dialog
+            .add_filter(extension.to_uppercase(), &[extension])
+            .add_filter("*", &["*"]),
+    }
+}
+
+/// Lowercase extension of the file, empty if there is none
+fn extension(file_name: &str) -> String {
+    std::path::Path::new(file_name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension("/tmp/map.SVG"), "svg");
+        assert_eq!(extension("marshrutka-map.png"), "png");
+        assert_eq!(extension("map"), "");
+    }
+}
@@ -0,0 +1,85 @@
+use crate::cell::Cell;
+use crate::emoji::EmojiCode;
+use crate::grid::{MapGrid, PoI};
+use crate::homeland::Homeland;
+use crate::index::CellIndex;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use strum::IntoEnumIterator;
+
+/// Target of a route, chosen by cell contents instead of exact position
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CellFilter {
+    CenterEmoji(EmojiCode),
+    CornerEmoji(EmojiCode),
+    PoI(PoI),
+    Homeland(Homeland),
+}
+
+impl CellFilter {
+    pub fn matches(&self, cell: &Cell) -> bool {
+        match self {
+            CellFilter::CenterEmoji(emoji_code) => {
+                cell.contents
+                    .center
+                    .as_ref()
+                    .and_then(|content| content.emoji())
+                    == Some(emoji_code.without_variation_selector())
+            }
+            CellFilter::CornerEmoji(emoji_code) => {
+                let emoji_code = Some(emoji_code.without_variation_selector());
+                cell.contents
+                    .corners()
+                    .any(|content| content.emoji() == emoji_code)
+            }
+            CellFilter::PoI(poi) => cell.contents.poi() == Some(*poi),
+            CellFilter::Homeland(homeland) => matches!(
+                cell.index,
+                CellIndex::Homeland { homeland: cell_homeland, .. } if cell_homeland == *homeland
+            ),
+        }
+    }
+
+    pub fn emoji(&self) -> EmojiCode {
+        match self {
+            CellFilter::CenterEmoji(emoji_code) | CellFilter::CornerEmoji(emoji_code) => {
+                emoji_code.clone()
+            }
+            CellFilter::PoI(poi) => poi.emoji(),
+            CellFilter::Homeland(homeland) => homeland.into(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            CellFilter::CenterEmoji(_) => t!("in_center").to_string(),
+            CellFilter::CornerEmoji(_) => t!("in_corner").to_string(),
+            CellFilter::PoI(poi) => t!(poi.name()).to_string(),
+            CellFilter::Homeland(homeland) => t!(homeland.name()).to_string(),
+        }
+    }
+
+    /// All filters, which can match at least one cell of the grid
+    pub fn available(grid: &MapGrid) -> Vec<CellFilter> {
+        let center_emojis: BTreeSet<_> = grid
+            .grid
+            .iter()
+            .filter(|cell| cell.contents.poi().is_none())
+            .filter_map(|cell| cell.contents.center.as_ref()?.emoji())
+            .collect();
+        let corner_emojis: BTreeSet<_> = grid
+            .grid
+            .iter()
+            .flat_map(|cell| cell.contents.corners())
+            .filter_map(|content| content.emoji())
+            .collect();
+        PoI::iter()
+            .filter(|&poi| !grid.poi[poi].is_empty())
+            .map(CellFilter::PoI)
+            .chain(Homeland::iter().map(CellFilter::Homeland))
+            .chain(center_emojis.into_iter().map(CellFilter::CenterEmoji))
+            .chain(corner_emojis.into_iter().map(CellFilter::CornerEmoji))
+            .collect()
+    }
+}
@@ -1,27 +1,49 @@
-use crate::cell::{Cell, CellElement, cell_parts};
-use crate::consts::{ARROW_TIP_CIRCLE, ARROW_WIDTH, CELL_SIZE, GRID_SPACING};
+use crate::cell::{Cell, CellElement, CellMarks};
+use crate::consts::{
+    ARROW_LANE_OFFSET, ARROW_TIP_CIRCLE, ARROW_WIDTH, CELL_ROUNDING, CELL_SIZE, CURSOR_CELL_STROKE,
+    GRID_SPACING, MAP_ZOOM_SPEED, MAX_MAP_ZOOM, MIN_MAP_HEIGHT, MIN_MAP_ZOOM,
+};
+use crate::content::CellContents;
+use crate::cost::{Command, MoveKind};
 use crate::emoji::EmojiMap;
 use crate::homeland::Homeland;
-use crate::index::{BorderDirection, CellIndex, CellIndexBuilder, Pos};
-use anyhow::{Result, anyhow};
+use crate::index::{Border, BorderDirection, CellIndex, CellIndexBuilder, CellIndexLiteral, Pos};
 use eframe::emath::Rot2;
 use egui::ahash::HashSet;
 use egui::ecolor::ParseHexColorError;
-use egui::{Color32, Grid, InnerResponse, Painter, Pos2, ScrollArea, Stroke, Ui, Vec2};
+use egui::{
+    Color32, EventFilter, Id, InnerResponse, Key, Painter, Pos2, Rangef, Rect, Sense, Stroke,
+    StrokeKind, Ui, Vec2, WidgetInfo, WidgetType,
+};
 use enum_map::{Enum, EnumMap};
 use num_integer::Roots;
+use serde::{Deserialize, Serialize};
 use simplecss::DeclarationTokenizer;
 use std::borrow::Cow;
 use std::cell::OnceCell;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::iter;
 use std::ops::Index;
-use strum::{EnumCount, IntoEnumIterator};
+use strum::{EnumCount, EnumIter, IntoEnumIterator, IntoStaticStr};
 use tl::HTMLTag;
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, EnumCount, Enum)]
+#[derive(
+    Eq,
+    PartialEq,
+    Hash,
+    Copy,
+    Clone,
+    Debug,
+    EnumCount,
+    Enum,
+    EnumIter,
+    IntoStaticStr,
+    Serialize,
+    Deserialize,
+)]
 pub enum PoI {
     Campfire,
     Fountain,
@@ -35,105 +57,414 @@ pub struct MapGrid {
     pub grid: Vec<Cell>,
     pub index: HashMap<CellIndex, usize>,
     pub poi: EnumMap<PoI, HashSet<CellIndex>>,
+    /// Malformed cells, which were recovered in [`ParseMode::Lenient`]
+    pub errors: Vec<MapGridError>,
+}
+
+/// Zoom and pan of the map canvas
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapView {
+    pub zoom: f32,
+    /// Offset of the top left cell from the top left corner of the canvas
+    pub pan: Vec2,
+    /// Cell, which is selected by keyboard
+    #[serde(skip)]
+    pub cursor: Option<CellIndex>,
 }
 
 pub struct MapGridResponse {
     pub centers: HashMap<CellIndex, Pos2>,
     pub left: Option<CellIndex>,
     pub right: Option<CellIndex>,
+    pub hovered: Option<CellIndex>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ParseMode {
+    /// Any malformed cell fails the whole map
+    #[default]
+    Strict,
+    /// Malformed cells are kept, marked as invalid and reported in [`MapGrid::errors`]
+    Lenient,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MapGridError {
+    Html(tl::ParseError),
+    NoMapGrid,
+    NotSquare(usize),
+    InvalidBgColor {
+        x: i8,
+        y: i8,
+        text: String,
+        error: ParseHexColorError,
+    },
+    UnindexableCell {
+        x: i8,
+        y: i8,
+        literal: Option<String>,
+        pos: Option<String>,
+    },
+    UnexpectedCenterPosition {
+        x: i8,
+        y: i8,
+    },
+    CenterNotFound,
+    OutOfBounds(CellIndex),
+    HomelandTooLarge(u8),
+}
+
+impl Default for MapView {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            cursor: None,
+        }
+    }
+}
+
+impl MapView {
+    fn origin(&self, rect: Rect) -> Pos2 {
+        rect.min + self.pan
+    }
+
+    fn cell_rect(&self, rect: Rect, col: usize, row: usize) -> Rect {
+        let step = CELL_SIZE + GRID_SPACING;
+        Rect::from_min_size(
+            self.origin(rect) + Vec2::new(col as f32, row as f32) * step * self.zoom,
+            Vec2::splat(CELL_SIZE * self.zoom),
+        )
+    }
+}
+
+impl ParseMode {
+    fn recover<T>(
+        self,
+        errors: &mut Vec<MapGridError>,
+        error: MapGridError,
+        recovered: Option<T>,
+    ) -> Result<T, MapGridError> {
+        match (self, recovered) {
+            (ParseMode::Lenient, Some(recovered)) => {
+                errors.push(error);
+                Ok(recovered)
+            }
+            _ => Err(error),
+        }
+    }
+}
+
+/// Cell as it is given by map source, before indexing
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CellSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg_color: Option<Color32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_left: Option<CellElement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_right: Option<CellElement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom_left: Option<CellElement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom_right: Option<CellElement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center: Option<CellElement>,
+}
+
+struct RawCell {
+    x: i8,
+    y: i8,
+    bg_color: Result<Option<Color32>, MapGridError>,
+    source: CellSource,
+    index: Result<CellIndex, MapGridError>,
+}
+
+type BgColorError = (String, ParseHexColorError);
+
+/// Builds [`MapGrid`] of any homeland size from cells given by [`CellIndex`].
+/// Homelands are laid out as on the game map: Blue in top left, Red in bottom left,
+/// Green in bottom right and Yellow in top right quadrant.
+#[derive(Clone, Debug)]
+pub struct MapGridBuilder {
+    homeland_size: u8,
+    bg_color: Option<Color32>,
+    cells: HashMap<CellIndex, CellSource>,
+}
+
+impl MapGridBuilder {
+    pub fn new(homeland_size: u8) -> Self {
+        Self {
+            homeland_size,
+            bg_color: None,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Background color of cells, which have none
+    pub fn bg_color(mut self, bg_color: Color32) -> Self {
+        self.bg_color = Some(bg_color);
+        self
+    }
+
+    /// Index elements of the source, `top_right` and `bottom_right`, are replaced on build.
+    /// Cell given again for the same index replaces the previous one.
+    pub fn cell(mut self, cell_index: CellIndex, source: CellSource) -> Self {
+        self.cells.insert(cell_index, source);
+        self
+    }
+
+    pub fn center(mut self, cell_index: CellIndex, center: impl Into<CellElement>) -> Self {
+        self.cells.entry(cell_index).or_default().center = Some(center.into());
+        self
+    }
+
+    pub fn poi(self, cell_index: CellIndex, poi: PoI) -> Self {
+        self.center(cell_index, poi.emoji())
+    }
+
+    pub fn build(self) -> Result<MapGrid, MapGridError> {
+        let max_coord = i8::try_from(self.homeland_size)
+            .map_err(|_| MapGridError::HomelandTooLarge(self.homeland_size))?;
+        if let Some(&cell_index) = self
+            .cells
+            .keys()
+            .find(|cell_index| !in_bounds(cell_index, self.homeland_size))
+        {
+            return Err(MapGridError::OutOfBounds(cell_index));
+        }
+        let mut cells = self.cells;
+        let sources = (-max_coord..=max_coord)
+            .flat_map(|y| (-max_coord..=max_coord).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let cell_index = index_by_layout(x, y);
+                let mut source = cells.remove(&cell_index).unwrap_or_default();
+                source.bg_color = source.bg_color.or(self.bg_color);
+                let (literal, pos) = match cell_index {
+                    CellIndex::Center => (None, CellIndex::Center.to_string()),
+                    CellIndex::Homeland { pos, .. } => {
+                        (Some(CellIndexLiteral::from(cell_index)), pos.to_string())
+                    }
+                    CellIndex::Border { shift, .. } => {
+                        (Some(CellIndexLiteral::from(cell_index)), shift.to_string())
+                    }
+                };
+                // Same elements as parsed from map source
+                source.bottom_right =
+                    literal.and_then(|literal| <&'static str>::from(literal).try_into().ok());
+                source.top_right = pos.as_str().try_into().ok();
+                source
+            })
+            .collect();
+        MapGrid::from_sources(sources, ParseMode::Strict)
+    }
 }
 
 impl MapGrid {
-    pub fn parse(s: &str) -> Result<Self> {
-        let dom = tl::parse(s, tl::ParserOptions::new())?;
+    pub fn parse(s: &str) -> Result<Self, MapGridError> {
+        Self::parse_with_mode(s, ParseMode::Strict)
+    }
+
+    pub fn parse_lenient(s: &str) -> Result<Self, MapGridError> {
+        Self::parse_with_mode(s, ParseMode::Lenient)
+    }
+
+    pub fn parse_with_mode(s: &str, mode: ParseMode) -> Result<Self, MapGridError> {
+        let dom = tl::parse(s, tl::ParserOptions::new()).map_err(MapGridError::Html)?;
         let parser = dom.parser();
         let map_grid = dom
             .get_elements_by_class_name("map-grid")
             .find_map(|node_handle| node_handle.get(parser).and_then(|node| node.as_tag()))
-            .ok_or_else(|| anyhow!("No map-grid elements found"))?;
+            .ok_or(MapGridError::NoMapGrid)?;
         let map_cells: Vec<_> = map_grid
             .children()
             .top()
             .iter()
             .filter_map(|node_handle| to_tag_with_class(node_handle, parser, "map-cell"))
             .collect();
-        let square_size = map_cells.len().sqrt();
-        if square_size * square_size != map_cells.len() {
-            return Err(anyhow!("Map grid is not square: {}", map_cells.len()));
+        Self::from_raw_sources(
+            map_cells.into_iter().map(|map_cell| {
+                let (bg_color, bg_color_error) = match parse_bg_color_from_style(map_cell) {
+                    Ok(bg_color) => (bg_color, None),
+                    Err(error) => (None, Some(error)),
+                };
+                (
+                    CellSource {
+                        bg_color,
+                        top_left: parse_cell_element(map_cell, parser, "top-left-text"),
+                        top_right: parse_cell_element(map_cell, parser, "top-right-text"),
+                        bottom_left: parse_cell_element(map_cell, parser, "bottom-left-text"),
+                        bottom_right: parse_cell_element(map_cell, parser, "bottom-right-text"),
+                        center: parse_text(map_cell, parser),
+                    },
+                    bg_color_error,
+                )
+            }),
+            mode,
+        )
+    }
+
+    /// Builds grid from cells in row major order, as they are given by map source
+    pub fn from_sources(sources: Vec<CellSource>, mode: ParseMode) -> Result<Self, MapGridError> {
+        Self::from_raw_sources(sources.into_iter().map(|source| (source, None)), mode)
+    }
+
+    /// Cells in row major order, as they can be passed to [`MapGrid::from_sources`]
+    pub fn sources(&self) -> Vec<CellSource> {
+        self.grid
+            .iter()
+            .map(|cell| CellSource {
+                bg_color: cell.bg_color,
+                top_left: cell.top_left.clone(),
+                top_right: cell.top_right.clone(),
+                bottom_left: cell.bottom_left.clone(),
+                bottom_right: cell.bottom_right.clone(),
+                center: cell.center.clone(),
+            })
+            .collect()
+    }
+
+    fn from_raw_sources(
+        sources: impl ExactSizeIterator<Item = (CellSource, Option<BgColorError>)>,
+        mode: ParseMode,
+    ) -> Result<Self, MapGridError> {
+        let square_size = sources.len().sqrt();
+        if square_size * square_size != sources.len() {
+            return Err(MapGridError::NotSquare(sources.len()));
         }
         let max_coord = square_size / 2;
         let max_coord_i = max_coord as isize;
-        let (mut grid, index): (Vec<_>, HashMap<_, _>) = map_cells
-            .into_iter()
-            .enumerate()
+        let raw_cells: Vec<_> = sources
             .scan((-max_coord_i, -max_coord_i), |(x, y), item| {
                 if x == &(max_coord_i + 1) {
                     *x = -max_coord_i;
                     *y += 1;
                 }
-                let ret = Some((*x, *y, item));
+                let ret = Some((*x as i8, *y as i8, item));
                 *x += 1;
                 ret
             })
-            .map(|(x, y, (i, map_cell))| {
-                let bg_color = parse_bg_color_from_style(map_cell)?;
-                let top_left = parse_cell_element(map_cell, parser, "top-left-text");
-                let top_right = parse_cell_element(map_cell, parser, "top-right-text");
-                let bottom_left = parse_cell_element(map_cell, parser, "bottom-left-text");
-                let bottom_right = parse_cell_element(map_cell, parser, "bottom-right-text");
-                let center = parse_text(map_cell, parser);
+            .map(|(x, y, (source, bg_color_error))| {
+                let bg_color = match bg_color_error {
+                    None => Ok(source.bg_color),
+                    Some((text, error)) => Err(MapGridError::InvalidBgColor { x, y, text, error }),
+                };
                 let index = (
-                    bottom_right.as_ref().map(Cow::from),
-                    top_right.as_ref().map(Cow::from),
+                    source.bottom_right.as_ref().map(Cow::from),
+                    source.top_right.as_ref().map(Cow::from),
                 )
                     .try_into()
-                    .map_err(|_| {
-                        anyhow!(
-                            "Can not index cell {} {}",
-                            bottom_right
-                                .as_ref()
-                                .map(|s| s.to_string())
-                                .unwrap_or_default(),
-                            top_right
-                                .as_ref()
-                                .map(|s| s.to_string())
-                                .unwrap_or_default()
-                        )
-                    })?;
-                let poi = cell_parts(&center);
-                Ok((
-                    Cell {
-                        bg_color,
-                        top_left,
-                        top_right,
-                        bottom_left,
-                        bottom_right,
-                        center,
-                        index,
-                        poi,
-                        x: x as i8,
-                        y: y as i8,
-                        nearest_campfire: OnceCell::default(),
-                    },
-                    (index, i),
-                ))
+                    .map_err(|_| MapGridError::UnindexableCell {
+                        x,
+                        y,
+                        literal: source.bottom_right.as_ref().map(|s| s.to_string()),
+                        pos: source.top_right.as_ref().map(|s| s.to_string()),
+                    });
+                RawCell {
+                    x,
+                    y,
+                    bg_color,
+                    source,
+                    index,
+                }
             })
-            .collect::<Result<_>>()?;
+            .collect();
+        // Every quadrant and half-axis of the map belongs to exactly one homeland or border,
+        // so position of unindexable cell is enough to restore its index
+        let literals: HashMap<_, CellIndexLiteral> = raw_cells
+            .iter()
+            .filter_map(|raw_cell| {
+                raw_cell.index.as_ref().ok().map(|&cell_index| {
+                    (
+                        (raw_cell.x.signum(), raw_cell.y.signum()),
+                        cell_index.into(),
+                    )
+                })
+            })
+            .collect();
+        let mut errors = vec![];
+        let mut grid = Vec::with_capacity(raw_cells.len());
+        let mut recovered_indices = Vec::with_capacity(raw_cells.len());
+        for raw_cell in raw_cells {
+            let RawCell { x, y, .. } = raw_cell;
+            let mut invalid = false;
+            recovered_indices.push(raw_cell.index.is_err());
+            let bg_color = match raw_cell.bg_color {
+                Ok(bg_color) => bg_color,
+                Err(error) => {
+                    invalid = true;
+                    mode.recover(&mut errors, error, Some(None))?
+                }
+            };
+            let index = match raw_cell.index {
+                Ok(index) if in_bounds(&index, max_coord as u8) => index,
+                Ok(index) => {
+                    invalid = true;
+                    *recovered_indices.last_mut().unwrap() = true;
+                    mode.recover(
+                        &mut errors,
+                        MapGridError::OutOfBounds(index),
+                        index_by_position(&literals, x, y),
+                    )?
+                }
+                Err(error) => {
+                    invalid = true;
+                    mode.recover(&mut errors, error, index_by_position(&literals, x, y))?
+                }
+            };
+            let CellSource {
+                top_left,
+                top_right,
+                bottom_left,
+                bottom_right,
+                center,
+                ..
+            } = raw_cell.source;
+            let contents = CellContents::new(&center, &top_left, &bottom_left);
+            grid.push(Cell {
+                bg_color,
+                top_left,
+                top_right,
+                bottom_left,
+                bottom_right,
+                center,
+                index,
+                contents,
+                x,
+                y,
+                invalid,
+                nearest_campfire: OnceCell::default(),
+            });
+        }
+        // Index points to the first cell with it, but a parsed index wins over one recovered
+        // from position. Cells left out of the index are reported as duplicates by validation.
+        let mut index = HashMap::with_capacity(grid.len());
+        for (i, (cell, &recovered)) in grid.iter().zip(&recovered_indices).enumerate() {
+            match index.entry(cell.index) {
+                Entry::Vacant(entry) => {
+                    entry.insert(i);
+                }
+                Entry::Occupied(mut entry) => {
+                    if recovered_indices[*entry.get()] && !recovered {
+                        entry.insert(i);
+                    }
+                }
+            }
+        }
         if let Some(i) = index.get(&CellIndex::Center) {
             let cell = &grid[*i];
             if cell.x != 0 || cell.y != 0 {
-                return Err(anyhow!(
-                    "Unexpected center position: ({}, {})",
-                    cell.x,
-                    cell.y
-                ));
+                return Err(MapGridError::UnexpectedCenterPosition {
+                    x: cell.x,
+                    y: cell.y,
+                });
             }
         } else {
-            return Err(anyhow!("Center is not found"));
+            return Err(MapGridError::CenterNotFound);
         }
         let (poi, poi_by_homeland) = grid
             .iter()
-            .filter_map(|cell| cell.poi.map(|poi| (poi, cell.index)))
+            .filter_map(|cell| cell.contents.poi().map(|poi| (poi, cell.index)))
             .fold(
                 (EnumMap::default(), EnumMap::default()),
                 |(mut acc_poi, mut acc_poi_by_homeland), (poi, cell_index)| {
@@ -150,6 +481,8 @@ impl MapGrid {
                 },
             );
         let nearest_campfires = Homeland::iter()
+            // Homeland without campfires has nowhere to escape to
+            .filter(|&homeland| !poi_by_homeland[PoI::Campfire][homeland].is_empty())
             .flat_map(|homeland| {
                 let campfires = &poi_by_homeland[PoI::Campfire][homeland];
                 let grid_ref = grid.as_slice();
@@ -205,7 +538,7 @@ impl MapGrid {
                                 {
                                     (0, 0)
                                 }
-                                _ => unreachable!(),
+                                _ => return None,
                             };
                             let i = xy_to_i(max_coord_i, square_size, proj_x, proj_y);
                             let index = grid_ref[i].index;
@@ -233,48 +566,222 @@ impl MapGrid {
             grid,
             index,
             poi,
+            errors,
         })
     }
 
-    pub fn ui_content(&self, ui: &mut Ui, emoji_map: &EmojiMap) -> InnerResponse<MapGridResponse> {
-        Grid::new("map_grid")
-            .striped(false)
-            .spacing(Vec2::splat(GRID_SPACING))
-            .min_col_width(CELL_SIZE)
-            .min_row_height(CELL_SIZE)
-            .show(ui, |ui| {
-                let mut left = None;
-                let mut right = None;
-                let centers = self
-                    .grid
-                    .iter()
-                    .enumerate()
-                    .map(|(i, cell)| {
-                        let center = ScrollArea::both()
-                            .id_salt(i)
-                            .show(ui, |ui| {
-                                let (center, new_left, new_right) = cell.ui_content(ui, emoji_map);
-                                if new_left.is_some() {
-                                    left = new_left;
-                                }
-                                if new_right.is_some() {
-                                    right = new_right;
-                                }
-                                center
-                            })
-                            .inner;
-                        if (i + 1) % self.square_size == 0 {
-                            ui.end_row();
-                        }
-                        (cell.index, center)
-                    })
-                    .collect();
-                MapGridResponse {
-                    centers,
-                    left,
-                    right,
+    /// Paints grid on a canvas, which is zoomed by mouse wheel or pinch and panned by dragging
+    pub fn ui_content(
+        &self,
+        ui: &mut Ui,
+        emoji_map: &EmojiMap,
+        marks: &HashMap<CellIndex, CellMarks>,
+        view: &mut MapView,
+    ) -> InnerResponse<MapGridResponse> {
+        let size = Vec2::new(
+            ui.available_width(),
+            ui.available_height().max(MIN_MAP_HEIGHT),
+        );
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let rect = response.rect;
+        let painter = painter.with_clip_rect(rect);
+
+        if response.double_clicked() {
+            *view = MapView {
+                cursor: view.cursor,
+                ..Default::default()
+            };
+        }
+        if response.dragged() {
+            view.pan += response.drag_delta();
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let (scroll, pinch) = ui.input(|i| (i.smooth_scroll_delta.y, i.zoom_delta()));
+            let zoom = (view.zoom * pinch * (scroll * MAP_ZOOM_SPEED).exp())
+                .clamp(MIN_MAP_ZOOM, MAX_MAP_ZOOM);
+            if zoom != view.zoom {
+                // Point under the pointer stays in place
+                view.pan += (pointer - view.origin(rect)) * (1.0 - zoom / view.zoom);
+                view.zoom = zoom;
+            }
+        }
+
+        let hovered = response
+            .hover_pos()
+            .and_then(|pointer| self.cell_at(view, rect, pointer));
+        let mut left = hovered
+            .filter(|_| response.clicked())
+            .map(|cell| cell.index);
+        let mut right = hovered
+            .filter(|_| response.secondary_clicked())
+            .map(|cell| cell.index);
+        if let Some(clicked) = left.or(right) {
+            view.cursor = Some(clicked);
+            response.request_focus();
+        }
+
+        let has_focus = response.has_focus();
+        if has_focus {
+            let (enter, shift) = self.keyboard_input(ui, response.id, view, rect);
+            match (enter, shift) {
+                (true, false) => left = view.cursor,
+                (true, true) => right = view.cursor,
+                _ => {}
+            }
+        }
+
+        let cursor = view.cursor.filter(|_| has_focus);
+        let centers = self
+            .grid
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let cell_rect = view.cell_rect(rect, i % self.square_size, i / self.square_size);
+                if rect.intersects(cell_rect) {
+                    cell.paint(
+                        ui,
+                        &painter,
+                        emoji_map,
+                        cell_rect,
+                        view.zoom,
+                        marks.get(&cell.index).copied().unwrap_or_default(),
+                    );
+                    if cursor == Some(cell.index) {
+                        painter.rect_stroke(
+                            cell_rect,
+                            CELL_ROUNDING * view.zoom,
+                            Stroke::new(CURSOR_CELL_STROKE, ui.visuals().selection.stroke.color),
+                            StrokeKind::Outside,
+                        );
+                    }
                 }
+                (cell.index, cell_rect.center())
             })
+            .collect();
+
+        let cursor_description = view
+            .cursor
+            .and_then(|cursor| self.index.get(&cursor))
+            .map(|&i| self.grid[i].description());
+        response.widget_info(|| {
+            WidgetInfo::labeled(
+                WidgetType::Other,
+                true,
+                cursor_description.as_deref().unwrap_or_default(),
+            )
+        });
+        #[cfg(feature = "accesskit")]
+        self.accesskit_cells(ui, &response, view, rect);
+
+        InnerResponse::new(
+            MapGridResponse {
+                centers,
+                left,
+                right,
+                hovered: hovered.map(|cell| cell.index),
+            },
+            response,
+        )
+    }
+
+    /// Moves cursor by arrow keys and keeps it visible.
+    /// Returns whether Enter is pressed and with Shift.
+    fn keyboard_input(&self, ui: &Ui, id: Id, view: &mut MapView, rect: Rect) -> (bool, bool) {
+        // Arrow keys move the cursor instead of the focus
+        ui.memory_mut(|memory| {
+            memory.set_focus_lock_filter(
+                id,
+                EventFilter {
+                    horizontal_arrows: true,
+                    vertical_arrows: true,
+                    ..Default::default()
+                },
+            )
+        });
+        let (dx, dy, enter, shift) = ui.input(|i| {
+            let axis = |less, more| i.key_pressed(more) as isize - i.key_pressed(less) as isize;
+            (
+                axis(Key::ArrowLeft, Key::ArrowRight),
+                axis(Key::ArrowUp, Key::ArrowDown),
+                i.key_pressed(Key::Enter),
+                i.modifiers.shift,
+            )
+        });
+        let i = view
+            .cursor
+            .and_then(|cursor| self.index.get(&cursor).copied())
+            .or_else(|| self.index.get(&CellIndex::Center).copied())
+            .unwrap_or_default();
+        let last = self.square_size as isize - 1;
+        let col = (i % self.square_size) as isize + dx;
+        let row = (i / self.square_size) as isize + dy;
+        let (col, row) = (col.clamp(0, last) as usize, row.clamp(0, last) as usize);
+        view.cursor = self
+            .grid
+            .get(row * self.square_size + col)
+            .map(|cell| cell.index);
+        if dx != 0 || dy != 0 {
+            let cell_rect = view.cell_rect(rect, col, row);
+            view.pan += Vec2::new(
+                scroll_into(rect.x_range(), cell_rect.x_range()),
+                scroll_into(rect.y_range(), cell_rect.y_range()),
+            );
+        }
+        (enter, shift)
+    }
+
+    /// Cells are painted on a canvas, so they are described for screen readers separately
+    #[cfg(feature = "accesskit")]
+    fn accesskit_cells(&self, ui: &Ui, response: &egui::Response, view: &MapView, rect: Rect) {
+        use egui::accesskit;
+
+        let ctx = ui.ctx();
+        ctx.accesskit_node_builder(response.id, |node| node.set_role(accesskit::Role::Grid));
+        ctx.with_accessibility_parent(response.id, || {
+            for (i, cell) in self.grid.iter().enumerate() {
+                let cell_rect = view.cell_rect(rect, i % self.square_size, i / self.square_size);
+                ctx.accesskit_node_builder(response.id.with(i), |node| {
+                    node.set_role(accesskit::Role::Cell);
+                    node.set_label(cell.description());
+                    node.set_bounds(accesskit::Rect {
+                        x0: cell_rect.min.x.into(),
+                        y0: cell_rect.min.y.into(),
+                        x1: cell_rect.max.x.into(),
+                        y1: cell_rect.max.y.into(),
+                    });
+                    node.set_row_index(i / self.square_size);
+                    node.set_column_index(i % self.square_size);
+                });
+            }
+        });
+    }
+
+    fn cell_at(&self, view: &MapView, rect: Rect, pointer: Pos2) -> Option<&Cell> {
+        let step = CELL_SIZE + GRID_SPACING;
+        let world = (pointer - view.origin(rect)) / view.zoom;
+        let (col, row) = ((world.x / step).floor(), (world.y / step).floor());
+        let in_spacing = world.x - col * step > CELL_SIZE || world.y - row * step > CELL_SIZE;
+        let square_size = self.square_size as f32;
+        if in_spacing || col < 0.0 || row < 0.0 || col >= square_size || row >= square_size {
+            return None;
+        }
+        self.grid
+            .get(row as usize * self.square_size + col as usize)
+    }
+
+    /// Indices of cells, which differ between grids or exist in only one of them
+    pub fn diff(&self, other: &MapGrid) -> HashSet<CellIndex> {
+        self.index
+            .keys()
+            .chain(other.index.keys())
+            .copied()
+            .filter(
+                |cell_index| match (self.index.get(cell_index), other.index.get(cell_index)) {
+                    (Some(&i), Some(&j)) => !self.grid[i].same_as(&other.grid[j]),
+                    _ => true,
+                },
+            )
+            .collect()
     }
 
     pub const fn homeland_size(&self) -> usize {
@@ -290,6 +797,17 @@ impl Index<&CellIndex> for MapGrid {
     }
 }
 
+/// Shift, which brings `inner` range into `outer` one
+fn scroll_into(outer: Rangef, inner: Rangef) -> f32 {
+    if inner.min < outer.min {
+        outer.min - inner.min
+    } else if inner.max > outer.max {
+        outer.max - inner.max
+    } else {
+        0.0
+    }
+}
+
 const fn xy_to_i(homeland_size: isize, square_size: usize, x: isize, y: isize) -> usize {
     (x + homeland_size) as usize + (y + homeland_size) as usize * square_size
 }
@@ -310,7 +828,7 @@ fn nearest_campfire(
             return Some(from);
         }
     }
-    let from_cell = &grid[index[&from]];
+    let from_cell = &grid[*index.get(&from)?];
     campfires
         .iter()
         .map(|&pos| CellIndexBuilder::Homeland { homeland, pos }.build())
@@ -334,6 +852,60 @@ pub fn arrow(painter: &Painter, rot: Rot2, tip_length: f32, from: Pos2, to: Pos2
     painter.circle_filled(to, ARROW_TIP_CIRCLE, color);
 }
 
+/// Lanes, which are taken by arrows between each pair of cells.
+/// Arrows of different kinds and repeated arrows over the same cells are drawn side by side.
+#[derive(Default)]
+pub struct ArrowLanes(HashMap<(CellIndex, CellIndex), Vec<i32>>);
+
+impl ArrowLanes {
+    /// Takes a free lane for the move and returns ends of its arrow,
+    /// or `None` if the move has no arrow
+    pub fn take(
+        &mut self,
+        command: &Command,
+        centers: &HashMap<CellIndex, Pos2>,
+        zoom: f32,
+    ) -> Option<(MoveKind, Pos2, Pos2)> {
+        let kind = command.aggregated_cost.kind()?;
+        self.take_move(kind, command.from, command.to, centers, zoom)
+    }
+
+    /// Same as [`ArrowLanes::take`] for a move, which is not a command of a route
+    pub fn take_move(
+        &mut self,
+        kind: MoveKind,
+        from_index: CellIndex,
+        to_index: CellIndex,
+        centers: &HashMap<CellIndex, Pos2>,
+        zoom: f32,
+    ) -> Option<(MoveKind, Pos2, Pos2)> {
+        let (Some(&from), Some(&to)) = (centers.get(&from_index), centers.get(&to_index)) else {
+            return None;
+        };
+        let pair = (from_index.min(to_index), from_index.max(to_index));
+        let taken = self.0.entry(pair).or_default();
+        let mut lane = match kind {
+            MoveKind::CentralMove | MoveKind::StandardMove => 0,
+            MoveKind::Caravan => 1,
+            MoveKind::ScrollOfEscape
+            | MoveKind::ScrollOfEscapeHQ
+            | MoveKind::ScrollOfEscapeForum => -1,
+        };
+        while taken.contains(&lane) {
+            lane += if lane < 0 { -1 } else { 1 };
+        }
+        taken.push(lane);
+        let (pair_from, pair_to) = (centers[&pair.0], centers[&pair.1]);
+        let normal = if pair_from == pair_to {
+            Vec2::ZERO
+        } else {
+            (pair_to - pair_from).normalized().rot90()
+        };
+        let offset = normal * (lane as f32 * ARROW_LANE_OFFSET * zoom);
+        Some((kind, from + offset, to + offset))
+    }
+}
+
 fn parse_cell_element(map_cell: &HTMLTag, parser: &tl::Parser, class: &str) -> Option<CellElement> {
     map_cell.children().top().iter().find_map(|node_handle| {
         to_tag_with_class(node_handle, parser, class)
@@ -368,7 +940,9 @@ fn parse_text(html_tag: &HTMLTag, parser: &tl::Parser) -> Option<CellElement> {
     })
 }
 
-fn parse_bg_color_from_style(html_tag: &HTMLTag) -> Result<Option<Color32>> {
+fn parse_bg_color_from_style(
+    html_tag: &HTMLTag,
+) -> Result<Option<Color32>, (String, ParseHexColorError)> {
     html_tag
         .attributes()
         .get("style")
@@ -377,27 +951,362 @@ fn parse_bg_color_from_style(html_tag: &HTMLTag) -> Result<Option<Color32>> {
             let style = style.as_utf8_str();
             DeclarationTokenizer::from(style.as_ref())
                 .find(|v| v.name == "background-color")
-                .map(|v| Color32::from_hex(v.value).map_err(ParseHexColorErrorWrapper))
+                .map(|v| Color32::from_hex(v.value).map_err(|e| (v.value.to_string(), e)))
         })
         .transpose()
-        .map_err(|e| e.into())
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-struct ParseHexColorErrorWrapper(ParseHexColorError);
+const fn in_bounds(cell_index: &CellIndex, homeland_size: u8) -> bool {
+    match *cell_index {
+        CellIndex::Center => true,
+        CellIndex::Homeland { pos, .. } => {
+            pos.x >= 1 && pos.x <= homeland_size && pos.y >= 1 && pos.y <= homeland_size
+        }
+        CellIndex::Border { shift, .. } => shift >= 1 && shift <= homeland_size,
+    }
+}
+
+fn index_by_layout(x: i8, y: i8) -> CellIndex {
+    let pos = Pos::from((x.unsigned_abs(), y.unsigned_abs()));
+    let homeland = match (x.signum(), y.signum()) {
+        (0, 0) => return CellIndex::Center,
+        (-1, 1) | (-1, 0) => Homeland::Red,
+        (1, 1) | (0, 1) => Homeland::Green,
+        (1, -1) | (1, 0) => Homeland::Yellow,
+        _ => Homeland::Blue,
+    };
+    CellIndexBuilder::Homeland { homeland, pos }.build()
+}
+
+fn index_by_position(
+    literals: &HashMap<(i8, i8), CellIndexLiteral>,
+    x: i8,
+    y: i8,
+) -> Option<CellIndex> {
+    if (x, y) == (0, 0) {
+        return Some(CellIndex::Center);
+    }
+    let pos = Pos::from((x.unsigned_abs(), y.unsigned_abs()));
+    Some(
+        match literals.get(&(x.signum(), y.signum()))? {
+            CellIndexLiteral::Center => CellIndexBuilder::Center,
+            CellIndexLiteral::Blue => CellIndexBuilder::Homeland {
+                homeland: Homeland::Blue,
+                pos,
+            },
+            CellIndexLiteral::Red => CellIndexBuilder::Homeland {
+                homeland: Homeland::Red,
+                pos,
+            },
+            CellIndexLiteral::Green => CellIndexBuilder::Homeland {
+                homeland: Homeland::Green,
+                pos,
+            },
+            CellIndexLiteral::Yellow => CellIndexBuilder::Homeland {
+                homeland: Homeland::Yellow,
+                pos,
+            },
+            CellIndexLiteral::YB => CellIndexBuilder::Border {
+                border: Border::YB,
+                shift: pos.x.max(pos.y),
+            },
+            CellIndexLiteral::BR => CellIndexBuilder::Border {
+                border: Border::BR,
+                shift: pos.x.max(pos.y),
+            },
+            CellIndexLiteral::RG => CellIndexBuilder::Border {
+                border: Border::RG,
+                shift: pos.x.max(pos.y),
+            },
+            CellIndexLiteral::GY => CellIndexBuilder::Border {
+                border: Border::GY,
+                shift: pos.x.max(pos.y),
+            },
+        }
+        .build(),
+    )
+}
 
-impl Error for ParseHexColorErrorWrapper {
+impl Display for MapGridError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapGridError::Html(e) => write!(f, "Can not parse html: {e}"),
+            MapGridError::NoMapGrid => write!(f, "No map-grid elements found"),
+            MapGridError::NotSquare(len) => write!(f, "Map grid is not square: {len}"),
+            MapGridError::InvalidBgColor { x, y, text, error } => write!(
+                f,
+                "Invalid background color '{text}' of cell at ({x}, {y}): {error:?}"
+            ),
+            MapGridError::UnindexableCell { x, y, literal, pos } => write!(
+                f,
+                "Can not index cell at ({x}, {y}): {} {}",
+                literal.as_deref().unwrap_or_default(),
+                pos.as_deref().unwrap_or_default()
+            ),
+            MapGridError::UnexpectedCenterPosition { x, y } => {
+                write!(f, "Unexpected center position: ({x}, {y})")
+            }
+            MapGridError::CenterNotFound => write!(f, "Center is not found"),
+            MapGridError::OutOfBounds(cell_index) => {
+                write!(f, "Cell {cell_index} is out of map bounds")
+            }
+            MapGridError::HomelandTooLarge(size) => {
+                write!(
+                    f,
+                    "Homeland size {size} is too large, at most {} is supported",
+                    i8::MAX
+                )
+            }
+        }
+    }
+}
+
+impl Error for MapGridError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        if let ParseHexColorError::InvalidInt(e) = &self.0 {
-            Some(e)
-        } else {
-            None
+        match self {
+            MapGridError::Html(e) => Some(e),
+            MapGridError::InvalidBgColor {
+                error: ParseHexColorError::InvalidInt(e),
+                ..
+            } => Some(e),
+            _ => None,
         }
     }
 }
 
-impl Display for ParseHexColorErrorWrapper {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn cell_html(x: isize, y: isize) -> String {
+        let (literal, pos) = match (x.signum(), y.signum()) {
+            (0, 0) => {
+                return r#"<div class="map-cell"><div class="top-right-text">0#0</div></div>"#
+                    .to_string();
+            }
+            (-1, -1) => ("B", format!("{}#{}", -x, -y)),
+            (-1, 1) => ("R", format!("{}#{}", -x, y)),
+            (1, 1) => ("G", format!("{x}#{y}")),
+            (1, -1) => ("Y", format!("{x}#{}", -y)),
+            (-1, 0) => ("BR", (-x).to_string()),
+            (0, 1) => ("RG", y.to_string()),
+            (1, 0) => ("GY", x.to_string()),
+            (0, -1) => ("YB", (-y).to_string()),
+            _ => unreachable!(),
+        };
+        let center = if x.abs() == 2 && y.abs() == 2 {
+            "\u{1f525}"
+        } else {
+            ""
+        };
+        format!(
+            r#"<div class="map-cell" style="background-color: #a0a0a0">{center}<div class="top-right-text">{pos}</div><div class="bottom-right-text">{literal}</div></div>"#
+        )
+    }
+
+    /// 5x5 map with campfires in the corners, with given cells replaced by raw html
+    pub(crate) fn map_html(overrides: &[(isize, isize, &str)]) -> String {
+        let cells: String = (-2..=2)
+            .flat_map(|y| (-2..=2).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                overrides
+                    .iter()
+                    .find(|&&(ox, oy, _)| (ox, oy) == (x, y))
+                    .map(|(_, _, html)| html.to_string())
+                    .unwrap_or_else(|| cell_html(x, y))
+            })
+            .collect();
+        format!(r#"<html><body><div class="map-grid">{cells}</div></body></html>"#)
+    }
+
+    #[test]
+    fn parse_strict() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        assert_eq!(grid.homeland_size(), 2);
+        assert!(grid.errors.is_empty());
+        assert!(grid.grid.iter().all(|cell| !cell.invalid));
+        assert_eq!(grid.poi[PoI::Campfire].len(), 4);
+        assert_eq!(
+            grid[&CellIndex::Homeland {
+                homeland: Homeland::Red,
+                pos: Pos { x: 2, y: 1 }
+            }]
+                .x,
+            -2
+        );
+    }
+
+    #[test]
+    fn parse_strict_unindexable() {
+        let broken = r#"<div class="map-cell"><div class="top-right-text">?</div></div>"#;
+        assert_eq!(
+            MapGrid::parse(&map_html(&[(1, 2, broken)])).err(),
+            Some(MapGridError::UnindexableCell {
+                x: 1,
+                y: 2,
+                literal: None,
+                pos: Some("?".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_lenient_unindexable() {
+        let broken = r#"<div class="map-cell" style="background-color: #zz"></div>"#;
+        let grid = MapGrid::parse_lenient(&map_html(&[(1, 2, broken)])).unwrap();
+        assert_eq!(grid.errors.len(), 2);
+        let cell = &grid[&CellIndex::Homeland {
+            homeland: Homeland::Green,
+            pos: Pos { x: 1, y: 2 },
+        }];
+        assert!(cell.invalid);
+        assert_eq!((cell.x, cell.y), (1, 2));
+        assert_eq!(cell.bg_color, None);
+        assert_eq!(grid.grid.iter().filter(|cell| cell.invalid).count(), 1);
+    }
+
+    #[test]
+    fn parse_border_shift_out_of_range() {
+        let broken = r#"<div class="map-cell" style="background-color: #a0a0a0"><div class="top-right-text">9</div><div class="bottom-right-text">GY</div></div>"#;
+        let html = map_html(&[(1, 0, broken)]);
+        let out_of_bounds = CellIndex::Border {
+            border: Border::GY,
+            shift: 9,
+        };
+        assert_eq!(
+            MapGrid::parse_with_mode(&html, ParseMode::Strict).err(),
+            Some(MapGridError::OutOfBounds(out_of_bounds))
+        );
+        let grid = MapGrid::parse_lenient(&html).unwrap();
+        assert_eq!(grid.errors, [MapGridError::OutOfBounds(out_of_bounds)]);
+        let cell = &grid[&CellIndex::Border {
+            border: Border::GY,
+            shift: 1,
+        }];
+        assert!(cell.invalid);
+        assert_eq!((cell.x, cell.y), (1, 0));
+    }
+
+    #[test]
+    fn parse_lenient_center() {
+        let broken = r#"<div class="map-cell"></div>"#;
+        let grid = MapGrid::parse_lenient(&map_html(&[(0, 0, broken)])).unwrap();
+        assert_eq!(
+            grid.errors,
+            [MapGridError::UnindexableCell {
+                x: 0,
+                y: 0,
+                literal: None,
+                pos: None,
+            }]
+        );
+        assert!(grid[&CellIndex::Center].invalid);
+    }
+
+    #[test]
+    fn diff() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        assert!(
+            grid.diff(&MapGrid::parse(&map_html(&[])).unwrap())
+                .is_empty()
+        );
+        // Campfire moved from corner to the neighbour cell
+        let no_campfire = r#"<div class="map-cell" style="background-color: #a0a0a0"><div class="top-right-text">2#2</div><div class="bottom-right-text">G</div></div>"#;
+        let campfire = r#"<div class="map-cell" style="background-color: #a0a0a0">🔥<div class="top-right-text">1#2</div><div class="bottom-right-text">G</div></div>"#;
+        let moved = MapGrid::parse(&map_html(&[(2, 2, no_campfire), (1, 2, campfire)])).unwrap();
+        let mut changed: Vec<_> = grid.diff(&moved).into_iter().collect();
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                CellIndex::Homeland {
+                    homeland: Homeland::Green,
+                    pos: Pos { x: 1, y: 2 }
+                },
+                CellIndex::Homeland {
+                    homeland: Homeland::Green,
+                    pos: Pos { x: 2, y: 2 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_matches_parsed() {
+        let builder = Homeland::iter().fold(
+            MapGridBuilder::new(2).bg_color(Color32::from_hex("#a0a0a0").unwrap()),
+            |builder, homeland| {
+                builder.poi(
+                    CellIndex::Homeland {
+                        homeland,
+                        pos: Pos { x: 2, y: 2 },
+                    },
+                    PoI::Campfire,
+                )
+            },
+        );
+        let built = builder.build().unwrap();
+        let parsed = MapGrid::parse(&map_html(&[])).unwrap();
+        // Center of the html map has no background
+        assert_eq!(built.diff(&parsed), HashSet::from_iter([CellIndex::Center]));
+        assert_eq!(built.poi[PoI::Campfire].len(), 4);
+        let center = &built[&CellIndex::Center];
+        assert_eq!((center.x, center.y), (0, 0));
+        assert_eq!(
+            center.nearest_campfire.get().unwrap()[Homeland::Red],
+            Some(CellIndex::Homeland {
+                homeland: Homeland::Red,
+                pos: Pos { x: 2, y: 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn builder_any_size() {
+        let fountain = CellIndex::Border {
+            border: Border::GY,
+            shift: 7,
+        };
+        let grid = MapGridBuilder::new(7)
+            .poi(fountain, PoI::Fountain)
+            .build()
+            .unwrap();
+        assert_eq!(grid.square_size, 15);
+        assert_eq!(
+            grid.poi[PoI::Fountain].iter().collect::<Vec<_>>(),
+            [&fountain]
+        );
+        assert_eq!((grid[&fountain].x, grid[&fountain].y), (7, 0));
+    }
+
+    #[test]
+    fn builder_out_of_bounds() {
+        let cell_index = CellIndex::Homeland {
+            homeland: Homeland::Blue,
+            pos: Pos { x: 3, y: 1 },
+        };
+        assert_eq!(
+            MapGridBuilder::new(2)
+                .poi(cell_index, PoI::Forum)
+                .build()
+                .err(),
+            Some(MapGridError::OutOfBounds(cell_index))
+        );
+    }
+
+    #[test]
+    fn builder_too_large() {
+        assert_eq!(
+            MapGridBuilder::new(128).build().err(),
+            Some(MapGridError::HomelandTooLarge(128))
+        );
+    }
+
+    #[test]
+    fn scroll_into_view() {
+        let outer = Rangef::new(0.0, 100.0);
+        assert_eq!(scroll_into(outer, Rangef::new(10.0, 20.0)), 0.0);
+        assert_eq!(scroll_into(outer, Rangef::new(-15.0, -5.0)), 15.0);
+        assert_eq!(scroll_into(outer, Rangef::new(95.0, 105.0)), -5.0);
     }
 }
@@ -0,0 +1,103 @@
+use crate::grid::{CellSource, MapGrid, MapGridError, ParseMode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use time::OffsetDateTime;
+
+pub const MAP_HISTORY_KEY: &str = "map_history";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapSnapshot {
+    pub fetched_at: OffsetDateTime,
+    pub cells: Vec<CellSource>,
+}
+
+/// Fetched maps, oldest first
+#[derive(Default, Serialize, Deserialize)]
+pub struct MapHistory {
+    snapshots: VecDeque<MapSnapshot>,
+}
+
+impl MapSnapshot {
+    pub fn new(fetched_at: OffsetDateTime, grid: &MapGrid) -> Self {
+        Self {
+            fetched_at,
+            cells: grid.sources(),
+        }
+    }
+
+    pub fn grid(&self) -> Result<MapGrid, MapGridError> {
+        MapGrid::from_sources(self.cells.clone(), ParseMode::Lenient)
+    }
+}
+
+impl MapHistory {
+    /// Adds snapshot, if it differs from the latest one, and drops the oldest snapshots above
+    /// `capacity`. Returns whether history was changed
+    pub fn push(&mut self, snapshot: MapSnapshot, capacity: usize) -> bool {
+        let is_new = self
+            .snapshots
+            .back()
+            .is_none_or(|latest| latest.cells != snapshot.cells);
+        if is_new {
+            self.snapshots.push_back(snapshot);
+        }
+        is_new | self.truncate(capacity)
+    }
+
+    /// Drops the oldest snapshots above `capacity`. Returns whether history was changed
+    pub fn truncate(&mut self, capacity: usize) -> bool {
+        let excess = self.snapshots.len().saturating_sub(capacity);
+        self.snapshots.drain(..excess);
+        excess > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&MapSnapshot> {
+        self.snapshots.get(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::tests::map_html;
+    use time::macros::datetime;
+
+    #[test]
+    fn push_dedup_and_cap() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let other = MapGrid::parse(&map_html(&[(
+            2,
+            2,
+            r#"<div class="map-cell"><div class="top-right-text">2#2</div><div class="bottom-right-text">G</div></div>"#,
+        )]))
+        .unwrap();
+        let mut history = MapHistory::default();
+        assert!(history.push(MapSnapshot::new(datetime!(2025-01-01 0:00 UTC), &grid), 2));
+        assert!(!history.push(MapSnapshot::new(datetime!(2025-01-01 1:00 UTC), &grid), 2));
+        assert!(history.push(MapSnapshot::new(datetime!(2025-01-01 2:00 UTC), &other), 2));
+        assert!(history.push(MapSnapshot::new(datetime!(2025-01-01 3:00 UTC), &grid), 2));
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history.get(0).unwrap().fetched_at,
+            datetime!(2025-01-01 2:00 UTC)
+        );
+        assert!(
+            history
+                .get(1)
+                .unwrap()
+                .grid()
+                .unwrap()
+                .diff(&grid)
+                .is_empty()
+        );
+        assert_eq!(history.get(0).unwrap().grid().unwrap().diff(&grid).len(), 1);
+    }
+}
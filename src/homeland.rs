@@ -96,15 +96,15 @@ impl Homeland {
     }
 }
 
-impl TryFrom<EmojiCode> for Homeland {
+impl TryFrom<&EmojiCode> for Homeland {
     type Error = ();
 
-    fn try_from(value: EmojiCode) -> Result<Self, Self::Error> {
-        Ok(match value {
-            EmojiCode('\u{1f1ea}', Some('\u{1f1fa}')) => Homeland::Blue,
-            EmojiCode('\u{1f1ee}', Some('\u{1f1f2}')) => Homeland::Red,
-            EmojiCode('\u{1f1f2}', Some('\u{1f1f4}')) => Homeland::Green,
-            EmojiCode('\u{1f1fb}', Some('\u{1f1e6}')) => Homeland::Yellow,
+    fn try_from(value: &EmojiCode) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "\u{1f1ea}\u{1f1fa}" => Homeland::Blue,
+            "\u{1f1ee}\u{1f1f2}" => Homeland::Red,
+            "\u{1f1f2}\u{1f1f4}" => Homeland::Green,
+            "\u{1f1fb}\u{1f1e6}" => Homeland::Yellow,
             _ => return Err(()),
         })
     }
@@ -118,13 +118,12 @@ impl From<&Homeland> for EmojiCode {
 
 impl From<Homeland> for EmojiCode {
     fn from(value: Homeland) -> Self {
-        match value {
-            Homeland::Blue => ('\u{1f1ea}', '\u{1f1fa}'),
-            Homeland::Red => ('\u{1f1ee}', '\u{1f1f2}'),
-            Homeland::Green => ('\u{1f1f2}', '\u{1f1f4}'),
-            Homeland::Yellow => ('\u{1f1fb}', '\u{1f1e6}'),
-        }
-        .into()
+        EmojiCode::from_static(match value {
+            Homeland::Blue => "\u{1f1ea}\u{1f1fa}",
+            Homeland::Red => "\u{1f1ee}\u{1f1f2}",
+            Homeland::Green => "\u{1f1f2}\u{1f1f4}",
+            Homeland::Yellow => "\u{1f1fb}\u{1f1e6}",
+        })
     }
 }
 impl Display for Homeland {
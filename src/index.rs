@@ -1,4 +1,6 @@
+use crate::emoji::EmojiCode;
 use crate::homeland::Homeland;
+use enum_map::Enum;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
@@ -19,6 +21,7 @@ use strum::{EnumCount, EnumIter, IntoStaticStr};
     Ord,
     PartialOrd,
     IntoStaticStr,
+    Enum,
 )]
 pub enum Border {
     BR,
@@ -389,6 +392,64 @@ impl Display for CellIndexCommandSuffix {
     }
 }
 
+impl FromStr for CellIndexCommandSuffix {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('_');
+        let index = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("0"), Some("0"), None, None) => CellIndex::Center,
+            (Some(homeland), Some(x), Some(y), None) => {
+                let mut homeland = homeland.chars();
+                let (Some(homeland), None) = (homeland.next(), homeland.next()) else {
+                    return Err(());
+                };
+                CellIndexBuilder::Homeland {
+                    homeland: homeland.to_ascii_uppercase().try_into()?,
+                    pos: Pos {
+                        x: x.parse().map_err(|_| ())?,
+                        y: y.parse().map_err(|_| ())?,
+                    },
+                }
+                .build()
+            }
+            (Some(border), Some(shift), None, None) => {
+                parse_as_border(&border.to_ascii_uppercase(), shift).ok_or(())?
+            }
+            _ => return Err(()),
+        };
+        Ok(Self(index))
+    }
+}
+
+impl CellIndex {
+    /// Parses any notation of cell index, which is used in game chats:
+    /// `B 3#4`, `BR 2` and `0#0`, command suffixes `b_3_4`, `br_2` and `0_0`
+    /// with or without a command, and homeland flag followed by position like `🇪🇺3#4`
+    pub fn parse_any(s: &str) -> Option<CellIndex> {
+        let s = s.trim();
+        let s = ["/go_direct_", "/car_"]
+            .into_iter()
+            .find_map(|command| s.strip_prefix(command))
+            .unwrap_or(s);
+        let upper = s
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_uppercase();
+        upper
+            .parse()
+            .ok()
+            .or_else(|| {
+                // Without a space, like `B3#4` or `BR2`
+                let (left, right) = upper.split_at(upper.find(|ch: char| ch.is_ascii_digit())?);
+                parse_as_homeland(left, right).or_else(|| parse_as_border(left, right))
+            })
+            .or_else(|| s.parse().ok().map(|CellIndexCommandSuffix(index)| index))
+            .or_else(|| parse_with_flag(s))
+    }
+}
+
 impl FromStr for Border {
     type Err = ();
 
@@ -455,6 +516,19 @@ fn parse_as_homeland(homeland: &str, pos: &str) -> Option<CellIndex> {
     )
 }
 
+fn parse_with_flag(s: &str) -> Option<CellIndex> {
+    // Flag is a pair of regional indicators
+    let split = s.char_indices().nth(2).map_or(s.len(), |(i, _)| i);
+    let (flag, pos) = s.split_at(split);
+    Some(
+        CellIndexBuilder::Homeland {
+            homeland: (&EmojiCode::from(flag.to_string())).try_into().ok()?,
+            pos: pos.trim().parse().ok()?,
+        }
+        .build(),
+    )
+}
+
 fn parse_as_border(border: &str, shift: &str) -> Option<CellIndex> {
     Some(
         CellIndexBuilder::Border {
@@ -499,3 +573,62 @@ adjacent_pos!(
     (adjacent_pos_u64, u64),
     (adjacent_pos_usize, usize)
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_any() {
+        let b_3_4 = CellIndex::Homeland {
+            homeland: Homeland::Blue,
+            pos: Pos { x: 3, y: 4 },
+        };
+        let yb_2 = CellIndex::Border {
+            border: Border::YB,
+            shift: 2,
+        };
+        for (s, expected) in [
+            ("B 3#4", Some(b_3_4)),
+            (" b  3#4 ", Some(b_3_4)),
+            ("B3#4", Some(b_3_4)),
+            ("b_3_4", Some(b_3_4)),
+            ("/go_direct_b_3_4", Some(b_3_4)),
+            ("\u{1f1ea}\u{1f1fa}3#4", Some(b_3_4)),
+            ("\u{1f1ea}\u{1f1fa} 3#4", Some(b_3_4)),
+            ("YB 2", Some(yb_2)),
+            ("yb_2", Some(yb_2)),
+            ("/car_yb_2", Some(yb_2)),
+            ("0#0", Some(CellIndex::Center)),
+            ("0_0", Some(CellIndex::Center)),
+            ("X 1#1", None),
+            ("b_3", None),
+            ("", None),
+        ] {
+            assert_eq!(CellIndex::parse_any(s), expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn command_suffix_round_trip() {
+        for index in [
+            CellIndex::Center,
+            CellIndex::Homeland {
+                homeland: Homeland::Green,
+                pos: Pos { x: 10, y: 1 },
+            },
+            CellIndex::Border {
+                border: Border::GY,
+                shift: 7,
+            },
+        ] {
+            let suffix = CellIndexCommandSuffix(index).to_string();
+            assert_eq!(
+                suffix
+                    .parse::<CellIndexCommandSuffix>()
+                    .map(|suffix| suffix.0),
+                Ok(index)
+            );
+        }
+    }
+}
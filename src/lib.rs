@@ -2,16 +2,30 @@ use rust_i18n::i18n;
 
 i18n!("locales", fallback = "en");
 
+mod alarm;
 pub mod app;
 mod binary_heap;
-mod cell;
+mod bookmark;
+mod calendar;
+pub mod cell;
+mod cell_input;
+mod clock;
 mod consts;
+mod content;
 mod cost;
 mod deep_link;
 mod emoji;
-mod grid;
-mod homeland;
-mod index;
+pub mod export;
+mod file_dialog;
+mod filter;
+pub mod grid;
+mod history;
+pub mod homeland;
+pub mod index;
+mod note;
 mod pathfinder;
 mod skill;
+pub mod theme;
 mod translation;
+mod trip;
+pub mod validation;
@@ -0,0 +1,139 @@
+use crate::index::CellIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// User's annotation of a cell, like "dangerous at night" with tag "good wood"
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CellNote {
+    pub text: String,
+    pub tags: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CellNotes(BTreeMap<CellIndex, CellNote>);
+
+impl CellNote {
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty() && self.tags.is_empty()
+    }
+
+    /// Tags as comma separated text
+    pub fn tags_text(&self) -> String {
+        self.tags
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn set_tags_text(&mut self, s: &str) {
+        self.tags = s
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+
+    /// Case insensitive search in text and tags
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        self.text.to_lowercase().contains(&query)
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(&query))
+    }
+}
+
+impl CellNotes {
+    pub fn get(&self, cell: &CellIndex) -> Option<&CellNote> {
+        self.0.get(cell)
+    }
+
+    /// Sets note of the cell, empty note removes it
+    pub fn set(&mut self, cell: CellIndex, note: CellNote) {
+        if note.is_empty() {
+            self.0.remove(&cell);
+        } else {
+            self.0.insert(cell, note);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&CellIndex, &CellNote)> {
+        self.0.iter()
+    }
+
+    pub fn search<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> impl Iterator<Item = (&'a CellIndex, &'a CellNote)> {
+        self.0.iter().filter(move |(_, note)| note.matches(query))
+    }
+
+    /// All tags in use
+    pub fn tags(&self) -> BTreeSet<&str> {
+        self.0
+            .values()
+            .flat_map(|note| note.tags.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Cells, which have any of the tags
+    pub fn tagged<'a>(
+        &'a self,
+        tags: &'a BTreeSet<String>,
+    ) -> impl Iterator<Item = CellIndex> + 'a {
+        self.0
+            .iter()
+            .filter(|(_, note)| !note.tags.is_disjoint(tags))
+            .map(|(&cell, _)| cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str, tags: &str) -> CellNote {
+        let mut note = CellNote {
+            text: text.to_string(),
+            ..Default::default()
+        };
+        note.set_tags_text(tags);
+        note
+    }
+
+    #[test]
+    fn tags_text() {
+        let note = note("", " good wood,, dangerous at night ,good wood");
+        assert_eq!(note.tags.len(), 2);
+        assert_eq!(note.tags_text(), "dangerous at night, good wood");
+    }
+
+    #[test]
+    fn search_and_tags() {
+        let wood = CellIndex::parse_any("B1#1").unwrap();
+        let night = CellIndex::parse_any("G2#2").unwrap();
+        let mut notes = CellNotes::default();
+        notes.set(wood, note("Lots of trees", "good wood"));
+        notes.set(night, note("Dangerous at night", "danger, good wood"));
+        notes.set(CellIndex::Center, note("  ", ""));
+
+        assert_eq!(notes.iter().count(), 2);
+        let found: Vec<_> = notes.search("NIGHT").map(|(&cell, _)| cell).collect();
+        assert_eq!(found, [night]);
+        let found: Vec<_> = notes.search("wood").map(|(&cell, _)| cell).collect();
+        assert_eq!(found.len(), 2);
+
+        assert_eq!(
+            notes.tags().into_iter().collect::<Vec<_>>(),
+            ["danger", "good wood"]
+        );
+        let danger = BTreeSet::from(["danger".to_string()]);
+        assert_eq!(notes.tagged(&danger).collect::<Vec<_>>(), [night]);
+
+        notes.set(night, CellNote::default());
+        assert!(notes.get(&night).is_none());
+    }
+}
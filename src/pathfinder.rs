@@ -1,4 +1,5 @@
 use crate::binary_heap::BinaryHeap;
+use crate::cell::Cell;
 use crate::consts::{CARAVAN_MONEY, CARAVAN_TIME, CARAVAN_TO_CENTER_MONEY, CARAVAN_TO_HOME_MONEY};
 use crate::cost::{CaravanCost, CostComparator, EdgeCost, TotalCost};
 use crate::grid::{MapGrid, PoI};
@@ -197,8 +198,20 @@ pub struct FindPath<'a> {
 
 impl FindPath<'_> {
     pub fn eval(self, from: CellIndex, to: CellIndex) -> Option<TotalCost> {
+        self.eval_nearest(from, |cell| cell.index == to)
+    }
+
+    /// Path to the first cell, which satisfies the `goal`, in order of `sort_by`
+    pub fn eval_nearest(self, from: CellIndex, goal: impl Fn(&Cell) -> bool) -> Option<TotalCost> {
         let start = TotalCost::new(from);
-        if from == to {
+        // Lenient maps may lack some indices, such cells are never the goal
+        let is_goal = |cell_index: &CellIndex| {
+            self.grid
+                .index
+                .get(cell_index)
+                .is_some_and(|&i| goal(&self.grid.grid[i]))
+        };
+        if is_goal(&from) {
             return Some(start);
         }
         let mut dist = HashMap::new();
@@ -218,7 +231,7 @@ impl FindPath<'_> {
         heap.push(start);
         while let Some(cost) = heap.pop() {
             let lowest_cost_index = cost.commands.last().unwrap().to;
-            if lowest_cost_index == to {
+            if is_goal(&lowest_cost_index) {
                 return Some(cost);
             }
             if comparator(&cost, &dist[&lowest_cost_index]).is_gt() {
@@ -274,8 +287,88 @@ fn caravan_cost(
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::cost::{AggregatedCost, Command};
+    use crate::filter::CellFilter;
+    use crate::grid::tests::map_html;
     use time::ext::NumericalDuration;
 
+    fn find_path(grid: &MapGrid) -> FindPath<'_> {
+        FindPath {
+            scroll_of_escape_cost: 50,
+            scroll_of_escape_hq_cost: 75,
+            scroll_of_escape_forum_cost: 100,
+            use_soe: false,
+            use_sfm: false,
+            use_caravans: false,
+            hq_position: None,
+            route_guru: RouteGuru(0),
+            fleetfoot: Fleetfoot(0),
+            sort_by: (CostComparator::Legs, CostComparator::Money),
+            homeland: Homeland::Blue,
+            grid,
+        }
+    }
+
+    #[test]
+    fn nearest_campfire() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let filter = CellFilter::PoI(PoI::Campfire);
+        let path = find_path(&grid)
+            .eval_nearest(CellIndex::Center, |cell| filter.matches(cell))
+            .unwrap();
+        assert_eq!(path.legs, 3);
+        let to = path.commands.last().unwrap().to;
+        assert!(filter.matches(&grid[&to]));
+    }
+
+    #[test]
+    fn nearest_with_missing_index() {
+        // Cell at G 1#2 claims to be G 2#2, so G 1#2 is missing from the index
+        let duplicate = r#"<div class="map-cell" style="background-color: #a0a0a0"><div class="top-right-text">2#2</div><div class="bottom-right-text">G</div></div>"#;
+        let grid = MapGrid::parse_lenient(&map_html(&[(1, 2, duplicate)])).unwrap();
+        let missing = CellIndex::parse_any("G1#2").unwrap();
+        assert!(!grid.index.contains_key(&missing));
+        assert!(
+            find_path(&grid)
+                .eval_nearest(CellIndex::Center, |_| false)
+                .is_none()
+        );
+        assert!(find_path(&grid).eval(CellIndex::Center, missing).is_none());
+    }
+
+    #[test]
+    fn nearest_homeland() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let filter = CellFilter::Homeland(Homeland::Green);
+        let path = find_path(&grid)
+            .eval_nearest(CellIndex::Center, |cell| filter.matches(cell))
+            .unwrap();
+        assert_eq!(path.legs, 1);
+        assert_eq!(
+            path.commands.last().unwrap().to,
+            CellIndex::Homeland {
+                homeland: Homeland::Green,
+                pos: Pos { x: 1, y: 1 }
+            }
+        );
+    }
+
+    #[test]
+    fn nearest_is_start() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        let path = find_path(&grid)
+            .eval_nearest(CellIndex::Center, |_| true)
+            .unwrap();
+        assert!(matches!(
+            path.commands[..],
+            [Command {
+                aggregated_cost: AggregatedCost::NoMove,
+                ..
+            }]
+        ));
+    }
+
     #[test]
     fn time_as_str() {
         let m = 63.minutes();
@@ -0,0 +1,244 @@
+use crate::cost::MoveKind;
+use egui::{Color32, Rgba, ThemePreference, Ui};
+use enum_map::EnumMap;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, EnumIter, IntoStaticStr, Default)]
+pub enum ThemeMode {
+    System,
+    #[default]
+    Dark,
+    Light,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, EnumIter, IntoStaticStr, Default)]
+pub enum Palette {
+    #[default]
+    Classic,
+    /// Okabe-Ito palette, distinguishable with any kind of colour blindness
+    ColorBlind,
+    Custom,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    pub palette: Palette,
+    /// Route colours of [`Palette::Custom`]
+    pub custom: EnumMap<MoveKind, Color32>,
+}
+
+impl ThemeMode {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+
+    pub const fn preference(&self) -> ThemePreference {
+        match self {
+            ThemeMode::System => ThemePreference::System,
+            ThemeMode::Dark => ThemePreference::Dark,
+            ThemeMode::Light => ThemePreference::Light,
+        }
+    }
+}
+
+impl Palette {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
+impl Theme {
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_theme(self.mode.preference());
+    }
+
+    /// Route colour of the move, `dark_mode` is whether the map is drawn with dark visuals
+    pub fn move_color(&self, kind: MoveKind, dark_mode: bool) -> Color32 {
+        match self.palette {
+            Palette::Classic => classic_color(kind, dark_mode),
+            Palette::ColorBlind => color_blind_color(kind, dark_mode),
+            Palette::Custom => self.custom[kind],
+        }
+    }
+
+    /// Returns whether theme is changed
+    pub fn settings_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        egui::ComboBox::new("theme_mode", t!("theme"))
+            .selected_text(t!(self.mode.name()))
+            .show_ui(ui, |ui| {
+                for mode in ThemeMode::iter() {
+                    if ui
+                        .selectable_value(&mut self.mode, mode, t!(mode.name()))
+                        .changed()
+                    {
+                        self.apply(ui.ctx());
+                        changed = true;
+                    }
+                }
+            });
+        egui::ComboBox::new("palette", t!("route_palette"))
+            .selected_text(t!(self.palette.name()))
+            .show_ui(ui, |ui| {
+                for palette in Palette::iter() {
+                    changed |= ui
+                        .selectable_value(&mut self.palette, palette, t!(palette.name()))
+                        .changed();
+                }
+            });
+        egui::Grid::new("route_colors").show(ui, |ui| {
+            for kind in MoveKind::iter() {
+                let mut color = self.move_color(kind, ui.visuals().dark_mode);
+                if ui.color_edit_button_srgba(&mut color).changed() {
+                    self.customize(ui.visuals().dark_mode);
+                    self.custom[kind] = color;
+                    changed = true;
+                }
+                ui.label(t!(kind.name()));
+                ui.end_row();
+            }
+        });
+        changed
+    }
+
+    /// Switches to [`Palette::Custom`], starting from colours of the current palette
+    pub fn customize(&mut self, dark_mode: bool) {
+        if self.palette != Palette::Custom {
+            self.custom = EnumMap::from_fn(|kind| self.move_color(kind, dark_mode));
+            self.palette = Palette::Custom;
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            mode: Default::default(),
+            palette: Default::default(),
+            custom: EnumMap::from_fn(|kind| classic_color(kind, true)),
+        }
+    }
+}
+
+/// Pinned routes are drawn in one colour each, which differs from colours of moves
+const PINNED_ROUTE_COLORS_DARK: [Color32; 6] = [
+    Color32::from_rgb(0x18, 0xff, 0xff),
+    Color32::from_rgb(0xff, 0x40, 0x81),
+    Color32::from_rgb(0xc6, 0xff, 0x00),
+    Color32::from_rgb(0xff, 0xab, 0x00),
+    Color32::from_rgb(0x82, 0xb1, 0xff),
+    Color32::from_rgb(0xbd, 0xbd, 0xbd),
+];
+const PINNED_ROUTE_COLORS_LIGHT: [Color32; 6] = [
+    Color32::from_rgb(0x00, 0x83, 0x8f),
+    Color32::from_rgb(0xc5, 0x11, 0x62),
+    Color32::from_rgb(0x82, 0x77, 0x17),
+    Color32::from_rgb(0xe6, 0x51, 0x00),
+    Color32::from_rgb(0x39, 0x49, 0xab),
+    Color32::from_rgb(0x61, 0x61, 0x61),
+];
+
+/// Colour of the `i`-th pinned route
+pub fn pinned_route_color(i: usize, dark_mode: bool) -> Color32 {
+    let colors = if dark_mode {
+        &PINNED_ROUTE_COLORS_DARK
+    } else {
+        &PINNED_ROUTE_COLORS_LIGHT
+    };
+    colors[i % colors.len()]
+}
+
+/// Black or white text, whichever is readable on `background`
+pub fn label_text_color(background: Color32) -> Color32 {
+    if Rgba::from(background).intensity() > 0.5 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    }
+}
+
+const fn classic_color(kind: MoveKind, dark_mode: bool) -> Color32 {
+    match kind {
+        MoveKind::CentralMove => Color32::RED,
+        MoveKind::StandardMove => Color32::BLUE,
+        MoveKind::Caravan => Color32::DARK_GREEN,
+        MoveKind::ScrollOfEscape => Color32::BROWN,
+        MoveKind::ScrollOfEscapeHQ if dark_mode => Color32::WHITE,
+        MoveKind::ScrollOfEscapeHQ => Color32::BLACK,
+        MoveKind::ScrollOfEscapeForum => Color32::PURPLE,
+    }
+}
+
+/// Yellow of the palette is hard to see on light backgrounds, black is used instead
+const fn color_blind_color(kind: MoveKind, dark_mode: bool) -> Color32 {
+    match kind {
+        MoveKind::CentralMove => Color32::from_rgb(0xd5, 0x5e, 0x00),
+        MoveKind::StandardMove => Color32::from_rgb(0x00, 0x72, 0xb2),
+        MoveKind::Caravan => Color32::from_rgb(0x00, 0x9e, 0x73),
+        MoveKind::ScrollOfEscape => Color32::from_rgb(0xe6, 0x9f, 0x00),
+        MoveKind::ScrollOfEscapeHQ if dark_mode => Color32::from_rgb(0xf0, 0xe4, 0x42),
+        MoveKind::ScrollOfEscapeHQ => Color32::BLACK,
+        MoveKind::ScrollOfEscapeForum => Color32::from_rgb(0xcc, 0x79, 0xa7),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn customize_keeps_palette_colors() {
+        let mut theme = Theme {
+            palette: Palette::ColorBlind,
+            ..Default::default()
+        };
+        let colors: Vec<_> = MoveKind::iter()
+            .map(|kind| theme.move_color(kind, false))
+            .collect();
+        theme.customize(false);
+        assert!(theme.palette == Palette::Custom);
+        assert_eq!(
+            MoveKind::iter()
+                .map(|kind| theme.move_color(kind, false))
+                .collect::<Vec<_>>(),
+            colors
+        );
+    }
+
+    #[test]
+    fn colors_depend_on_mode() {
+        let theme = Theme::default();
+        let shq = MoveKind::ScrollOfEscapeHQ;
+        assert_eq!(theme.move_color(shq, true), Color32::WHITE);
+        assert_eq!(theme.move_color(shq, false), Color32::BLACK);
+    }
+
+    #[test]
+    fn pinned_routes_differ_from_moves() {
+        for palette in [Palette::Classic, Palette::ColorBlind] {
+            let theme = Theme {
+                palette,
+                ..Default::default()
+            };
+            for dark_mode in [false, true] {
+                for i in 0..PINNED_ROUTE_COLORS_DARK.len() {
+                    let color = pinned_route_color(i, dark_mode);
+                    assert!(
+                        MoveKind::iter().all(|kind| theme.move_color(kind, dark_mode) != color),
+                        "pinned colour {color:?} is a move colour"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn label_text_contrasts() {
+        assert_eq!(label_text_color(Color32::WHITE), Color32::BLACK);
+        assert_eq!(label_text_color(Color32::BLUE), Color32::WHITE);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::cost::Command;
+use crate::index::CellIndex;
+use time::{Duration, OffsetDateTime};
+
+/// Command of a started trip, which is ticked when it is sent
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TripStep {
+    pub command: Command,
+    pub scheduled_at: OffsetDateTime,
+    pub sent_at: Option<OffsetDateTime>,
+}
+
+/// Route, which is being travelled, as a checklist of commands
+#[derive(Clone, Debug)]
+pub struct Trip {
+    started_at: OffsetDateTime,
+    /// Cell, where the trip ends, it is kept on re-planning
+    destination: CellIndex,
+    /// Arrival target of the original plan, it is kept on re-planning
+    arrive_at: OffsetDateTime,
+    steps: Vec<TripStep>,
+}
+
+impl Trip {
+    pub fn new(
+        schedule: impl IntoIterator<Item = (Command, OffsetDateTime)>,
+        destination: CellIndex,
+        arrive_at: OffsetDateTime,
+        now: OffsetDateTime,
+    ) -> Self {
+        let mut trip = Self {
+            started_at: now,
+            destination,
+            arrive_at,
+            steps: Vec::new(),
+        };
+        trip.replan(schedule);
+        trip
+    }
+
+    pub fn started_at(&self) -> OffsetDateTime {
+        self.started_at
+    }
+
+    pub fn destination(&self) -> CellIndex {
+        self.destination
+    }
+
+    pub fn arrive_at(&self) -> OffsetDateTime {
+        self.arrive_at
+    }
+
+    pub fn steps(&self) -> &[TripStep] {
+        &self.steps
+    }
+
+    /// Ticks or unticks step
+    pub fn set_sent(&mut self, step: usize, sent: bool, now: OffsetDateTime) {
+        if let Some(step) = self.steps.get_mut(step) {
+            step.sent_at = sent.then_some(now);
+        }
+    }
+
+    /// First step, which is not sent yet
+    pub fn next_step(&self) -> Option<&TripStep> {
+        self.steps.iter().find(|step| step.sent_at.is_none())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_step().is_none()
+    }
+
+    pub fn elapsed(&self, now: OffsetDateTime) -> Duration {
+        now - self.started_at
+    }
+
+    /// How late the next command is, negative when there is time left.
+    /// After the last command it is how late the last command was sent.
+    pub fn lateness(&self, now: OffsetDateTime) -> Duration {
+        match self.next_step() {
+            Some(step) => now - step.scheduled_at,
+            None => self
+                .steps
+                .last()
+                .and_then(|step| Some(step.sent_at? - step.scheduled_at))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Where the traveller should be now
+    pub fn current_cell(&self) -> Option<CellIndex> {
+        self.steps
+            .iter()
+            .rev()
+            .find(|step| step.sent_at.is_some())
+            .map(|step| step.command.to)
+            .or_else(|| self.steps.first().map(|step| step.command.from))
+    }
+
+    /// Replaces commands, which are not sent yet. Sent ones are kept as history.
+    pub fn replan(&mut self, schedule: impl IntoIterator<Item = (Command, OffsetDateTime)>) {
+        self.steps.retain(|step| step.sent_at.is_some());
+        self.steps.extend(
+            schedule
+                .into_iter()
+                .map(|(command, scheduled_at)| TripStep {
+                    command,
+                    scheduled_at,
+                    sent_at: None,
+                }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::AggregatedCost;
+    use time::macros::datetime;
+
+    fn command(from: &str, to: &str) -> Command {
+        Command {
+            aggregated_cost: AggregatedCost::CentralMove {
+                time: Duration::minutes(5),
+            },
+            from: CellIndex::parse_any(from).unwrap(),
+            to: CellIndex::parse_any(to).unwrap(),
+        }
+    }
+
+    #[test]
+    fn checklist_and_lateness() {
+        let start = datetime!(2025-01-01 12:00:00 UTC);
+        let arrive_at = start + Duration::minutes(10);
+        let mut trip = Trip::new(
+            [
+                (command("0#0", "B1#1"), start),
+                (command("B1#1", "B2#2"), start + Duration::minutes(5)),
+            ],
+            CellIndex::parse_any("B2#2").unwrap(),
+            arrive_at,
+            start - Duration::minutes(1),
+        );
+        assert_eq!(trip.current_cell(), Some(CellIndex::Center));
+        assert_eq!(trip.lateness(start), Duration::ZERO);
+
+        trip.set_sent(0, true, start + Duration::seconds(10));
+        assert_eq!(trip.current_cell(), CellIndex::parse_any("B1#1"));
+        assert_eq!(
+            trip.lateness(start + Duration::minutes(6)),
+            Duration::minutes(1)
+        );
+        assert_eq!(
+            trip.lateness(start + Duration::minutes(4)),
+            -Duration::minutes(1)
+        );
+
+        trip.set_sent(1, true, start + Duration::minutes(6));
+        assert!(trip.is_finished());
+        assert_eq!(
+            trip.lateness(start + Duration::hours(1)),
+            Duration::minutes(1)
+        );
+        assert_eq!(trip.elapsed(start), Duration::minutes(1));
+    }
+
+    #[test]
+    fn replan_keeps_sent_steps() {
+        let start = datetime!(2025-01-01 12:00:00 UTC);
+        let mut trip = Trip::new(
+            [
+                (command("0#0", "B1#1"), start),
+                (command("B1#1", "B2#2"), start + Duration::minutes(5)),
+            ],
+            CellIndex::parse_any("B2#2").unwrap(),
+            start + Duration::minutes(10),
+            start,
+        );
+        trip.set_sent(0, true, start);
+        trip.replan([(command("G1#1", "B2#2"), start + Duration::minutes(4))]);
+        let steps: Vec<_> = trip
+            .steps()
+            .iter()
+            .map(|step| (step.command.to, step.sent_at.is_some()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (CellIndex::parse_any("B1#1").unwrap(), true),
+                (CellIndex::parse_any("B2#2").unwrap(), false),
+            ]
+        );
+        assert_eq!(trip.arrive_at(), start + Duration::minutes(10));
+        assert_eq!(trip.destination(), CellIndex::parse_any("B2#2").unwrap());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::grid::{MapGrid, PoI};
+use crate::homeland::Homeland;
+use crate::index::{Border, BorderDirection, CellIndex, Pos};
+use enum_map::EnumMap;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use strum::IntoEnumIterator;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MapIssue {
+    HomelandSize {
+        homeland: Homeland,
+        cells: usize,
+        expected: usize,
+    },
+    BorderShiftMissing {
+        border: Border,
+        shift: u8,
+    },
+    BorderShiftOutOfRange {
+        border: Border,
+        shift: u8,
+    },
+    CoordinatesMismatch {
+        index: CellIndex,
+        x: i8,
+        y: i8,
+    },
+    QuadrantMismatch {
+        index: CellIndex,
+        x: i8,
+        y: i8,
+    },
+    MisplacedBorder(Border),
+    NoCampfire(Homeland),
+    DuplicateIndex {
+        index: CellIndex,
+        x: i8,
+        y: i8,
+    },
+}
+
+/// Checks invariants of parsed map, which are assumed by path finding
+pub fn validate(grid: &MapGrid) -> Vec<MapIssue> {
+    let homeland_size = grid.homeland_size();
+    let mut issues = vec![];
+
+    // Only one cell of the same index is in the index of the grid
+    for (i, cell) in grid.grid.iter().enumerate() {
+        if grid.index.get(&cell.index) != Some(&i) {
+            issues.push(MapIssue::DuplicateIndex {
+                index: cell.index,
+                x: cell.x,
+                y: cell.y,
+            });
+        }
+    }
+
+    let mut homeland_cells = EnumMap::<Homeland, usize>::default();
+    let mut border_shifts = EnumMap::<Border, BTreeSet<u8>>::default();
+    // Sign of coordinates of the first met cell defines quadrant of homeland
+    let mut quadrants = EnumMap::<Homeland, Option<(i8, i8)>>::default();
+    for cell in &grid.grid {
+        let (x, y) = (cell.x, cell.y);
+        let coordinates_match = match cell.index {
+            CellIndex::Center => x == 0 && y == 0,
+            CellIndex::Homeland {
+                homeland,
+                pos: Pos { x: pos_x, y: pos_y },
+            } => {
+                homeland_cells[homeland] += 1;
+                let quadrant = *quadrants[homeland].get_or_insert((x.signum(), y.signum()));
+                if quadrant != (x.signum(), y.signum()) {
+                    issues.push(MapIssue::QuadrantMismatch {
+                        index: cell.index,
+                        x,
+                        y,
+                    });
+                }
+                x.unsigned_abs() == pos_x && y.unsigned_abs() == pos_y
+            }
+            CellIndex::Border { border, shift } => {
+                border_shifts[border].insert(shift);
+                match border.direction() {
+                    BorderDirection::Horizontal => y == 0 && x.unsigned_abs() == shift,
+                    BorderDirection::Vertical => x == 0 && y.unsigned_abs() == shift,
+                }
+            }
+        };
+        if !coordinates_match {
+            issues.push(MapIssue::CoordinatesMismatch {
+                index: cell.index,
+                x,
+                y,
+            });
+        }
+    }
+
+    for homeland in Homeland::iter() {
+        let expected = homeland_size * homeland_size;
+        if homeland_cells[homeland] != expected {
+            issues.push(MapIssue::HomelandSize {
+                homeland,
+                cells: homeland_cells[homeland],
+                expected,
+            });
+        }
+    }
+
+    for border in Border::iter() {
+        let shifts = &border_shifts[border];
+        issues.extend(
+            (1..=homeland_size as u8)
+                .filter(|shift| !shifts.contains(shift))
+                .map(|shift| MapIssue::BorderShiftMissing { border, shift }),
+        );
+        issues.extend(
+            shifts
+                .iter()
+                .filter(|&&shift| shift == 0 || shift as usize > homeland_size)
+                .map(|&shift| MapIssue::BorderShiftOutOfRange { border, shift }),
+        );
+
+        // Border lies on the axis, which separates its neighbours
+        let [a, b] = border.neighbours().map(|homeland| quadrants[homeland]);
+        let Some(((a_x, a_y), (b_x, b_y))) = a.zip(b) else {
+            continue;
+        };
+        let half_axis = match border.direction() {
+            BorderDirection::Horizontal if a_x == b_x && a_y == -b_y => (a_x, 0),
+            BorderDirection::Vertical if a_y == b_y && a_x == -b_x => (0, a_y),
+            _ => {
+                issues.push(MapIssue::MisplacedBorder(border));
+                continue;
+            }
+        };
+        issues.extend(
+            grid.grid
+                .iter()
+                .filter(
+                    |cell| matches!(cell.index, CellIndex::Border { border: b, .. } if b == border),
+                )
+                .filter(|cell| (cell.x.signum(), cell.y.signum()) != half_axis)
+                .map(|cell| MapIssue::QuadrantMismatch {
+                    index: cell.index,
+                    x: cell.x,
+                    y: cell.y,
+                }),
+        );
+    }
+
+    let campfires = &grid.poi[PoI::Campfire];
+    issues.extend(
+        Homeland::iter()
+            .filter(|&homeland| {
+                !campfires.iter().any(|campfire| {
+                    matches!(campfire, CellIndex::Homeland { homeland: h, .. } if *h == homeland)
+                })
+            })
+            .map(MapIssue::NoCampfire),
+    );
+
+    issues
+}
+
+impl Display for MapIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapIssue::HomelandSize {
+                homeland,
+                cells,
+                expected,
+            } => write!(f, "{homeland} has {cells} cells, expected {expected}"),
+            MapIssue::BorderShiftMissing { border, shift } => {
+                write!(f, "Border {border} misses cell {border} {shift}")
+            }
+            MapIssue::BorderShiftOutOfRange { border, shift } => {
+                write!(f, "Border {border} has out of range cell {border} {shift}")
+            }
+            MapIssue::CoordinatesMismatch { index, x, y } => {
+                write!(f, "Cell {index} does not match its position ({x}, {y})")
+            }
+            MapIssue::QuadrantMismatch { index, x, y } => {
+                write!(f, "Cell {index} at ({x}, {y}) is out of its quadrant")
+            }
+            MapIssue::MisplacedBorder(border) => {
+                write!(f, "Border {border} does not separate its neighbours")
+            }
+            MapIssue::NoCampfire(homeland) => write!(f, "{homeland} has no campfires"),
+            MapIssue::DuplicateIndex { index, x, y } => {
+                write!(f, "Cell {index} at ({x}, {y}) is duplicated")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::tests::map_html;
+
+    #[test]
+    fn valid_map() {
+        let grid = MapGrid::parse(&map_html(&[])).unwrap();
+        assert_eq!(validate(&grid), []);
+    }
+
+    #[test]
+    fn no_campfire() {
+        let no_campfire = r#"<div class="map-cell"><div class="top-right-text">2#2</div><div class="bottom-right-text">Y</div></div>"#;
+        let grid = MapGrid::parse(&map_html(&[(2, -2, no_campfire)])).unwrap();
+        assert_eq!(validate(&grid), [MapIssue::NoCampfire(Homeland::Yellow)]);
+    }
+
+    #[test]
+    fn recovered_index_collision() {
+        // Cell at G 1#2 has no index, G 2#2 is labelled as G 1#2 and keeps that index
+        let unindexable = r#"<div class="map-cell"></div>"#;
+        let mislabelled = r#"<div class="map-cell">🔥<div class="top-right-text">1#2</div><div class="bottom-right-text">G</div></div>"#;
+        let grid =
+            MapGrid::parse_lenient(&map_html(&[(1, 2, unindexable), (2, 2, mislabelled)])).unwrap();
+        let green_1_2 = CellIndex::Homeland {
+            homeland: Homeland::Green,
+            pos: Pos { x: 1, y: 2 },
+        };
+        assert_eq!((grid[&green_1_2].x, grid[&green_1_2].y), (2, 2));
+        assert!(validate(&grid).contains(&MapIssue::DuplicateIndex {
+            index: green_1_2,
+            x: 1,
+            y: 2
+        }));
+    }
+
+    #[test]
+    fn misplaced_cells() {
+        let duplicate = r#"<div class="map-cell"><div class="top-right-text">1#1</div><div class="bottom-right-text">R</div></div>"#;
+        let shifted = r#"<div class="map-cell"><div class="top-right-text">1</div><div class="bottom-right-text">GY</div></div>"#;
+        let grid = MapGrid::parse(&map_html(&[(-2, 1, duplicate), (2, 0, shifted)])).unwrap();
+        let red_1_1 = CellIndex::Homeland {
+            homeland: Homeland::Red,
+            pos: Pos { x: 1, y: 1 },
+        };
+        let gy_1 = CellIndex::Border {
+            border: Border::GY,
+            shift: 1,
+        };
+        assert_eq!(
+            validate(&grid),
+            [
+                MapIssue::DuplicateIndex {
+                    index: gy_1,
+                    x: 2,
+                    y: 0
+                },
+                MapIssue::DuplicateIndex {
+                    index: red_1_1,
+                    x: -1,
+                    y: 1
+                },
+                MapIssue::CoordinatesMismatch {
+                    index: gy_1,
+                    x: 2,
+                    y: 0
+                },
+                MapIssue::CoordinatesMismatch {
+                    index: red_1_1,
+                    x: -2,
+                    y: 1
+                },
+                MapIssue::BorderShiftMissing {
+                    border: Border::GY,
+                    shift: 2
+                },
+            ]
+        );
+    }
+}